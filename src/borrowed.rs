@@ -0,0 +1,236 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    BorrowedToken, Config, Entry, ParseError, ParseOptions, ParseWarning, Span, insert_with_policy,
+    normalize_key, pattern, split_entries, tokenize_borrowed,
+};
+
+// === 借用する Config ===
+//
+// キーと値を入力の &'a str のまま持つ Config。たくさんの小さな設定を検証するときに、
+// キー・値・コメントごとの文字列の確保を避けるために使う。
+// 引用符を外した値、継続行をつないだ値、/ 区切りを正規化したキーだけは新しい文字列になる。
+// Config::history（重複も含めたすべての定義）はキーごとの表を作らず、読んだ順の1つの Vec に並べておき、
+// into_owned で初めてキーごとにまとめる。
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BorrowedEntry<'a> {
+    raw_key: Cow<'a, str>,
+    value: Cow<'a, str>,
+    raw_value: Cow<'a, str>,
+    ignore_error: bool,
+    key_span: Span,
+    value_span: Span,
+}
+
+impl<'a> BorrowedEntry<'a> {
+    pub fn raw_key(&self) -> &str {
+        &self.raw_key
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn raw_value(&self) -> &str {
+        &self.raw_value
+    }
+
+    pub fn ignore_error(&self) -> bool {
+        self.ignore_error
+    }
+
+    pub fn line_number(&self) -> usize {
+        self.key_span.line
+    }
+
+    pub fn key_span(&self) -> Span {
+        self.key_span
+    }
+
+    pub fn value_span(&self) -> Span {
+        self.value_span
+    }
+
+    pub fn into_owned(self) -> Entry {
+        Entry {
+            raw_key: self.raw_key.into_owned(),
+            value: self.value.into_owned(),
+            raw_value: self.raw_value.into_owned(),
+            ignore_error: self.ignore_error,
+            key_span: self.key_span,
+            value_span: self.value_span,
+            source: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BorrowedConfig<'a> {
+    entries: HashMap<Cow<'a, str>, Vec<BorrowedEntry<'a>>>,
+    globs: Vec<(Cow<'a, str>, BorrowedEntry<'a>)>,
+    excluded: HashSet<Cow<'a, str>>,
+    // グロブ以外のすべての定義（読んだ順）
+    definitions: Vec<(Cow<'a, str>, BorrowedEntry<'a>)>,
+    warnings: Vec<ParseWarning>,
+}
+
+impl<'a> BorrowedConfig<'a> {
+    pub fn parse(content: &'a str) -> Result<Self, ParseError> {
+        BorrowedConfig::parse_with(content, &ParseOptions::default())
+    }
+
+    // Config::parse_with と同じく、include の行は読み飛ばす
+    pub fn parse_with(content: &'a str, options: &ParseOptions) -> Result<Self, ParseError> {
+        let mut config = BorrowedConfig::default();
+        for raw in split_entries(content, options) {
+            let token = tokenize_borrowed(raw.raw, raw.line_number, raw.offset, options)?;
+            if let BorrowedToken::KeyValue {
+                key,
+                raw_key,
+                value,
+                raw_value,
+                ignore_error,
+                key_span,
+                value_span,
+            } = token
+            {
+                let entry = BorrowedEntry {
+                    raw_key,
+                    value,
+                    raw_value,
+                    ignore_error,
+                    key_span,
+                    value_span,
+                };
+                if pattern::is_glob(&key) {
+                    config.globs.push((key, entry));
+                    continue;
+                }
+                config.definitions.push((key.clone(), entry.clone()));
+                insert_with_policy(
+                    &mut config.entries,
+                    &mut config.warnings,
+                    key,
+                    entry,
+                    BorrowedEntry::key_span,
+                    options.duplicates,
+                )?;
+            } else if let BorrowedToken::Exclusion { key, .. } = token {
+                config.excluded.insert(key);
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entry(key).map(|e| e.value())
+    }
+
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.entries
+            .get(normalize_key(key).as_ref())
+            .map(|entries| entries.iter().map(|e| e.value()).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn entry(&self, key: &str) -> Option<&BorrowedEntry<'a>> {
+        self.entries
+            .get(normalize_key(key).as_ref())
+            .and_then(|entries| entries.last())
+    }
+
+    pub fn globs(&self) -> impl Iterator<Item = (&str, &BorrowedEntry<'a>)> {
+        self.globs
+            .iter()
+            .map(|(pattern, entry)| (pattern.as_ref(), entry))
+    }
+
+    pub fn is_excluded(&self, key: &str) -> bool {
+        self.excluded.contains(normalize_key(key).as_ref())
+    }
+
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    // 借用をやめて Config にする。Config::parse_with で読んだのと同じものになる
+    pub fn into_owned(self) -> Config {
+        fn owned<'a>(
+            map: HashMap<Cow<'a, str>, Vec<BorrowedEntry<'a>>>,
+        ) -> HashMap<String, Vec<Entry>> {
+            map.into_iter()
+                .map(|(key, entries)| {
+                    let entries = entries.into_iter().map(BorrowedEntry::into_owned);
+                    (key.into_owned(), entries.collect())
+                })
+                .collect()
+        }
+        let mut history: HashMap<String, Vec<Entry>> = HashMap::new();
+        for (key, entry) in self.definitions {
+            history
+                .entry(key.into_owned())
+                .or_default()
+                .push(entry.into_owned());
+        }
+        Config {
+            entries: owned(self.entries),
+            globs: self
+                .globs
+                .into_iter()
+                .map(|(pattern, entry)| (pattern.into_owned(), entry.into_owned()))
+                .collect(),
+            excluded: self.excluded.into_iter().map(Cow::into_owned).collect(),
+            history,
+            warnings: self.warnings,
+        }
+    }
+}
+
+// === テスト ===
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::arb_config_content;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn into_owned_matches_config_parse(content in arb_config_content()) {
+            let borrowed = BorrowedConfig::parse(&content).unwrap().into_owned();
+            let owned = Config::parse(&content).unwrap();
+            prop_assert_eq!(borrowed.entries, owned.entries);
+            prop_assert_eq!(borrowed.history, owned.history);
+        }
+    }
+
+    #[test]
+    fn plain_keys_and_values_are_borrowed() {
+        let content =
+            "# comment\nnet.ipv4.ip_forward = 1\nnet/ipv4/tcp_syncookies = 1\nmotd = \"hi\"\n";
+        let options = ParseOptions {
+            quoted_values: true,
+            ..ParseOptions::default()
+        };
+        let config = BorrowedConfig::parse_with(content, &options).unwrap();
+        let (key, entries) = config.entries.get_key_value("net.ipv4.ip_forward").unwrap();
+        assert!(matches!(key, Cow::Borrowed(_)));
+        assert!(matches!(entries[0].value, Cow::Borrowed("1")));
+        // 重複を含めた定義の記録もキーを借用したまま
+        assert!(matches!(
+            config.definitions[0].0,
+            Cow::Borrowed("net.ipv4.ip_forward")
+        ));
+        // / 区切りのキーと引用符付きの値だけは新しく確保する
+        let (key, _) = config
+            .entries
+            .get_key_value("net.ipv4.tcp_syncookies")
+            .unwrap();
+        assert!(matches!(key, Cow::Owned(_)));
+        let motd = config.entry("motd").unwrap();
+        assert_eq!(motd.value(), "hi");
+        assert!(matches!(motd.raw_value, Cow::Borrowed("\"hi\"")));
+    }
+}
@@ -0,0 +1,537 @@
+use std::collections::{BTreeMap, btree_map};
+use std::fmt;
+
+use serde::de::value::{BorrowedStrDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
+
+use crate::typed::{ValueError, conversion_error, parse_bool, parse_integer};
+use crate::{Config, GetError, Span};
+
+// === serde によるデシリアライズ ===
+//
+// feature = "serde" のときだけ使える。ドット区切りのキーを入れ子の構造体やマップにする。
+//
+//   log.file = /var/log/console.log     →  struct Log { file: PathBuf }
+//   net.ipv4.conf.eth0.rp_filter = 1    →  conf: HashMap<String, Iface>
+//
+// bool と整数は ValueType と同じ書き方だけを受け付ける。書かれていないキーは Option なら None になる。
+// 空白で区切った値（ip_local_port_range = 32768 60999）はタプルや Vec にできる。
+// グロブのキーは対象にならないので、必要なら先に Config::expand で展開しておく。
+
+// === エラー型 ===
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeError {
+    // 値を型に変換できない、または必須のフィールドが無い
+    Value(GetError),
+    // それ以外の serde のエラー。key が空なら Config 全体、span は値の位置（分かるとき）
+    Custom {
+        key: String,
+        message: String,
+        span: Option<Span>,
+    },
+}
+
+impl fmt::Display for DeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeError::Value(error) => write!(f, "{}", error),
+            DeError::Custom {
+                key,
+                message,
+                span: Some(span),
+            } => write!(f, "line {}: '{}': {}", span.line, key, message),
+            DeError::Custom { key, message, .. } if key.is_empty() => write!(f, "{}", message),
+            DeError::Custom { key, message, .. } => write!(f, "'{}': {}", key, message),
+        }
+    }
+}
+
+impl std::error::Error for DeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DeError::Value(error) => Some(error),
+            DeError::Custom { .. } => None,
+        }
+    }
+}
+
+// visitor が作るエラーはどのキーのものか分からないので、locate で後から付ける
+impl de::Error for DeError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        DeError::Custom {
+            key: String::new(),
+            message: message.to_string(),
+            span: None,
+        }
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        DeError::Value(GetError::Missing {
+            key: field.to_string(),
+        })
+    }
+}
+
+// path の位置で visitor が作ったエラーに、キーと span を付ける
+fn locate(path: &str, span: Option<Span>, error: DeError) -> DeError {
+    match error {
+        DeError::Value(GetError::Missing { key }) => DeError::Value(GetError::Missing {
+            key: join(path, &key),
+        }),
+        DeError::Custom { message, .. } => DeError::Custom {
+            key: path.to_string(),
+            message,
+            span,
+        },
+        error => error,
+    }
+}
+
+// どちらかが空ならもう一方だけ（ser.rs では値そのものの行のキーが空になる）
+pub(crate) fn join(path: &str, segment: &str) -> String {
+    match (path.is_empty(), segment.is_empty()) {
+        (true, _) => segment.to_string(),
+        (_, true) => path.to_string(),
+        _ => format!("{}.{}", path, segment),
+    }
+}
+
+// === キーの木 ===
+
+// log.file と log.level は log の下の file と level になる。
+// log = x と log.file = y が両方あれば、値としても表としても読める
+#[derive(Debug, Default)]
+struct Node<'de> {
+    value: Option<(&'de str, Span)>,
+    children: BTreeMap<&'de str, Node<'de>>,
+}
+
+impl<'de> Node<'de> {
+    fn new(config: &'de Config) -> Self {
+        let mut root = Node::default();
+        for (key, entry) in config.iter() {
+            let mut node = &mut root;
+            for segment in key.split('.') {
+                node = node.children.entry(segment).or_default();
+            }
+            node.value = Some((entry.value(), entry.value_span()));
+        }
+        root
+    }
+
+    fn leaf(value: &'de str, span: Span) -> Self {
+        Node {
+            value: Some((value, span)),
+            children: BTreeMap::new(),
+        }
+    }
+
+    // エラーで指す位置。表なら最初の子
+    fn span(&self) -> Option<Span> {
+        self.value
+            .map(|(_, span)| span)
+            .or_else(|| self.children.values().find_map(Node::span))
+    }
+}
+
+// === Deserializer ===
+
+pub struct Deserializer<'de> {
+    node: Node<'de>,
+    // ここまでのキー（Config 全体なら空）
+    path: String,
+}
+
+impl<'de> Deserializer<'de> {
+    pub fn from_config(config: &'de Config) -> Self {
+        Deserializer {
+            node: Node::new(config),
+            path: String::new(),
+        }
+    }
+
+    fn value(&self) -> Result<(&'de str, Span), DeError> {
+        self.node.value.ok_or_else(|| DeError::Custom {
+            key: self.path.clone(),
+            message: "expected a value, found a table of keys".to_string(),
+            span: None,
+        })
+    }
+
+    fn parse<T>(
+        &self,
+        expected: &str,
+        parse: impl FnOnce(&str) -> Result<T, ValueError>,
+    ) -> Result<T, DeError> {
+        let (value, span) = self.value()?;
+        parse(value).map_err(|error| {
+            DeError::Value(conversion_error(&self.path, expected, value, span, error))
+        })
+    }
+
+    fn locate(&self, error: DeError) -> DeError {
+        locate(&self.path, self.node.span(), error)
+    }
+}
+
+// T: Deserialize<'de> なら値の文字列を借用できる
+pub fn from_config<'de, T: de::Deserialize<'de>>(config: &'de Config) -> Result<T, DeError> {
+    T::deserialize(Deserializer::from_config(config))
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident($ty:ty, $expected:expr, $parse:expr);)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+            let parsed: $ty = self.parse($expected, $parse)?;
+            visitor.$visit(parsed).map_err(|e| self.locate(e))
+        }
+    )*};
+}
+
+fn from_str<T: std::str::FromStr>(value: &str) -> Result<T, ValueError> {
+    value.parse().map_err(|_| ValueError::Invalid)
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.node.value {
+            Some((value, _)) if self.node.children.is_empty() => visitor
+                .visit_borrowed_str(value)
+                .map_err(|e| self.locate(e)),
+            _ => self.deserialize_map(visitor),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool(bool, "bool", parse_bool);
+        deserialize_i8 => visit_i8(i8, "i8", parse_integer);
+        deserialize_i16 => visit_i16(i16, "i16", parse_integer);
+        deserialize_i32 => visit_i32(i32, "i32", parse_integer);
+        deserialize_i64 => visit_i64(i64, "integer", parse_integer);
+        deserialize_u8 => visit_u8(u8, "u8", parse_integer);
+        deserialize_u16 => visit_u16(u16, "u16", parse_integer);
+        deserialize_u32 => visit_u32(u32, "u32", parse_integer);
+        deserialize_u64 => visit_u64(u64, "u64", parse_integer);
+        deserialize_f32 => visit_f32(f32, "f32", from_str);
+        deserialize_f64 => visit_f64(f64, "f64", from_str);
+        deserialize_char => visit_char(char, "char", from_str);
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        let (value, _) = self.value()?;
+        visitor
+            .visit_borrowed_str(value)
+            .map_err(|e| self.locate(e))
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        let (value, _) = self.value()?;
+        visitor
+            .visit_borrowed_bytes(value.as_bytes())
+            .map_err(|e| self.locate(e))
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    // 書かれていないフィールドは serde が None にするので、ここに来るのは値があるときだけ
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // 値を空白で区切った並び。要素のエラーも値全体を指す
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        let (value, span) = self.value()?;
+        let words = value.split_whitespace().map(|word| Deserializer {
+            node: Node::leaf(word, span),
+            path: self.path.clone(),
+        });
+        let mut seq = SeqDeserializer::new(words);
+        let parsed = visitor.visit_seq(&mut seq).map_err(|e| self.locate(e))?;
+        seq.end().map_err(|e| self.locate(e))?;
+        Ok(parsed)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, DeError> {
+        if self.node.children.is_empty()
+            && let Some((_, span)) = self.node.value
+        {
+            return Err(DeError::Custom {
+                key: self.path,
+                message: "expected a table of keys, found a value".to_string(),
+                span: Some(span),
+            });
+        }
+        let mut entries = Entries {
+            path: &self.path,
+            children: std::mem::take(&mut self.node.children).into_iter(),
+            next: None,
+            failed: false,
+        };
+        match visitor.visit_map(&mut entries) {
+            Ok(parsed) => Ok(parsed),
+            // 子で起きたエラーには子が位置を付けている
+            Err(error) if entries.failed => Err(error),
+            Err(error) => Err(locate(&self.path, None, error)),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.deserialize_map(visitor)
+    }
+
+    // 値をバリアント名とするユニットバリアントだけ
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        let (value, _) = self.value()?;
+        visitor
+            .visit_enum(BorrowedStrDeserializer::<DeError>::new(value))
+            .map_err(|e| self.locate(e))
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DeError> for Deserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+// 表の子を名前の順に渡す
+struct Entries<'a, 'de> {
+    path: &'a str,
+    children: btree_map::IntoIter<&'de str, Node<'de>>,
+    // next_key_seed で取り出し、next_value_seed に渡す子
+    next: Option<(&'de str, Node<'de>)>,
+    // 子のデシリアライズで失敗した
+    failed: bool,
+}
+
+impl<'de> MapAccess<'de> for Entries<'_, 'de> {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, DeError> {
+        let Some((segment, node)) = self.children.next() else {
+            return Ok(None);
+        };
+        let span = node.span();
+        self.next = Some((segment, node));
+        // deny_unknown_fields のエラーなどはそのキーを指す
+        seed.deserialize(BorrowedStrDeserializer::<DeError>::new(segment))
+            .map(Some)
+            .map_err(|e| {
+                self.failed = true;
+                locate(&join(self.path, segment), span, e)
+            })
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, DeError> {
+        let (segment, node) = self
+            .next
+            .take()
+            .ok_or_else(|| de::Error::custom("value requested before its key"))?;
+        let child = Deserializer {
+            node,
+            path: join(self.path, segment),
+        };
+        seed.deserialize(child).inspect_err(|_| self.failed = true)
+    }
+}
+
+// === テスト ===
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Settings {
+        endpoint: String,
+        retry: u8,
+        debug: Option<bool>,
+        log: Log,
+        net: Net,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Log {
+        file: PathBuf,
+        level: Level,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Level {
+        Info,
+        Debug,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Net {
+        ipv4: Ipv4,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Ipv4 {
+        conf: HashMap<String, Iface>,
+        ip_local_port_range: (u16, u16),
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Iface {
+        rp_filter: i64,
+    }
+
+    const CONTENT: &str = "\
+endpoint = localhost:3000
+retry = 3
+log.file = /var/log/console.log
+log.level = info
+net.ipv4.conf.eth0.rp_filter = 1
+net/ipv4/conf/all/rp_filter = 2
+net.ipv4.ip_local_port_range = 32768 60999
+";
+
+    #[test]
+    fn dotted_keys_become_nested_structs_and_maps() {
+        let config = Config::parse(CONTENT).unwrap();
+        let settings: Settings = from_config(&config).unwrap();
+        assert_eq!(settings.endpoint, "localhost:3000");
+        assert_eq!(settings.retry, 3);
+        assert_eq!(settings.debug, None);
+        assert_eq!(
+            settings.log,
+            Log {
+                file: PathBuf::from("/var/log/console.log"),
+                level: Level::Info,
+            }
+        );
+        assert_eq!(settings.net.ipv4.conf["all"].rp_filter, 2);
+        assert_eq!(settings.net.ipv4.conf.len(), 2);
+        assert_eq!(settings.net.ipv4.ip_local_port_range, (32768, 60999));
+    }
+
+    #[test]
+    fn conversion_errors_cite_the_key_and_line() {
+        let content = CONTENT.replace("retry = 3", "retry = 300");
+        let config = Config::parse(&content).unwrap();
+        let err = from_config::<Settings>(&config).unwrap_err();
+        assert!(matches!(
+            &err,
+            DeError::Value(GetError::OutOfRange { key, expected, span, .. })
+                if key == "retry" && expected == "u8" && span.line == 2
+        ));
+        assert_eq!(
+            err.to_string(),
+            "line 2: 'retry': '300' is out of range for u8"
+        );
+
+        // bool は true / false だけ（ValueType::Bool と同じ）
+        let config = Config::parse(&format!("{}debug = yes\n", CONTENT)).unwrap();
+        assert!(matches!(
+            from_config::<Settings>(&config).unwrap_err(),
+            DeError::Value(GetError::Invalid { key, .. }) if key == "debug"
+        ));
+
+        let config = Config::parse(&CONTENT.replace("log.level = info\n", "")).unwrap();
+        assert_eq!(
+            from_config::<Settings>(&config).unwrap_err(),
+            DeError::Value(GetError::Missing {
+                key: "log.level".to_string()
+            })
+        );
+
+        let config = Config::parse(&CONTENT.replace("= info", "= loud")).unwrap();
+        let err = from_config::<Settings>(&config).unwrap_err();
+        assert!(matches!(
+            &err,
+            DeError::Custom { key, span: Some(span), .. } if key == "log.level" && span.line == 4
+        ));
+    }
+
+    #[test]
+    fn unknown_fields_point_at_their_key() {
+        #[derive(Debug, Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Strict {
+            retry: u8,
+        }
+        let config = Config::parse("retry = 1\n").unwrap();
+        assert_eq!(from_config::<Strict>(&config).unwrap().retry, 1);
+        let config = Config::parse("retry = 1\nretyr = 2\n").unwrap();
+        let err = from_config::<Strict>(&config).unwrap_err();
+        assert!(matches!(
+            err,
+            DeError::Custom { key, span: Some(span), .. } if key == "retyr" && span.line == 2
+        ));
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{ParseError, Schema};
+
+// === 構造体からスキーマを作る ===
+//
+// #[derive(SysctlSchema)]（feature = "derive"）が実装する。
+// フィールドの型が ValueType になり、入れ子の構造体はドット区切りの接頭辞、
+// HashMap と BTreeMap はパターンの * になる。doc コメントは SchemaEntry::description になる。
+//
+//   #[derive(SysctlSchema)]
+//   struct Log {
+//       /// 出力先
+//       file: PathBuf,
+//       #[sysctl(optional)]
+//       level: String,
+//   }
+//
+// から次のスキーマを作る。
+//
+//   # 出力先
+//   file = string
+//   level = string?
+//
+// 型とスキーマの型の対応:
+//   bool                        → bool
+//   i8 〜 i128, u8 〜 u128 など → integer
+//   String, PathBuf, f64 など   → string（Vec やタプルも空白区切りの string）
+//   Option<T>                   → T を省略可能にしたもの
+
+pub trait SysctlSchema {
+    // スキーマファイルの形式で out に書く。キーには prefix を付け、optional ならすべて省略可能にする
+    fn write_schema(prefix: &str, optional: bool, out: &mut String);
+
+    fn schema_text() -> String {
+        let mut out = String::new();
+        Self::write_schema("", false, &mut out);
+        out
+    }
+
+    // rename でキーの書式に合わない名前を付けるとエラーになる
+    fn schema() -> Result<Schema, ParseError> {
+        Schema::parse(&Self::schema_text())
+    }
+}
@@ -0,0 +1,589 @@
+use std::io::{self, IsTerminal, Write};
+
+#[cfg(feature = "serde")]
+use crate::DeError;
+use crate::{GetError, InterpolationError, ParseError, ParseWarning, Span, ValidationError};
+
+// === 診断メッセージ ===
+//
+// rustc 風に、ファイル名・行・列・該当行・下線・help をまとめて表示する。
+//
+//   error: 'retry' expects integer, got 'abc'
+//    --> sysctl.conf:3:9
+//     |
+//   3 | retry = abc
+//     |         ^^^ not a valid integer
+//     |
+//     = help: expected integer: a 64-bit signed integer such as `3` or `-1`
+
+// Renderer で表示できるエラー
+pub trait Diagnostic {
+    fn message(&self) -> String;
+    // None のときは該当行を表示しない
+    fn span(&self) -> Option<Span>;
+    // 下線の横に出す短い説明
+    fn label(&self) -> String;
+    fn help(&self) -> Option<String> {
+        None
+    }
+}
+
+impl Diagnostic for ParseError {
+    fn message(&self) -> String {
+        match self {
+            ParseError::InvalidLine { .. } => "invalid syntax".to_string(),
+            ParseError::InvalidType { type_name, .. } => format!("unknown type '{}'", type_name),
+            ParseError::DuplicateKey { key, .. } => format!("'{}' is defined more than once", key),
+            ParseError::ConflictingPatterns { pattern, other, .. } => {
+                format!("pattern '{}' conflicts with '{}'", pattern, other)
+            }
+            ParseError::UnterminatedQuote { .. } => "unterminated quote".to_string(),
+            ParseError::InvalidEscape { escape, .. } => {
+                format!("unknown escape sequence '{}'", escape)
+            }
+            ParseError::InvalidKey { invalid: None, .. } => "empty key segment".to_string(),
+            ParseError::InvalidKey { segment, .. } => format!("invalid key segment '{}'", segment),
+            ParseError::InvalidDefault { default, .. } => format!("invalid default '{}'", default),
+            ParseError::PatternDefault { pattern, .. } => {
+                format!("pattern '{}' cannot have a default", pattern)
+            }
+        }
+    }
+
+    fn span(&self) -> Option<Span> {
+        match self {
+            ParseError::InvalidLine { span, .. }
+            | ParseError::InvalidType { span, .. }
+            | ParseError::DuplicateKey { span, .. }
+            | ParseError::ConflictingPatterns { span, .. }
+            | ParseError::UnterminatedQuote { span, .. }
+            | ParseError::InvalidEscape { span, .. }
+            | ParseError::InvalidKey { span, .. }
+            | ParseError::InvalidDefault { span, .. }
+            | ParseError::PatternDefault { span, .. } => Some(*span),
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            ParseError::InvalidLine { .. } => "expected `key = value`".to_string(),
+            ParseError::InvalidType { .. } => "unknown type".to_string(),
+            ParseError::DuplicateKey { .. } => "defined again here".to_string(),
+            ParseError::ConflictingPatterns { .. } => "overlaps with a different type".to_string(),
+            ParseError::UnterminatedQuote { .. } => "missing closing quote".to_string(),
+            ParseError::InvalidEscape { .. } => "unknown escape".to_string(),
+            ParseError::InvalidKey {
+                invalid: Some(c), ..
+            } => format!("`{}` is not allowed here", c),
+            ParseError::InvalidKey { invalid: None, .. } => "empty segment".to_string(),
+            ParseError::InvalidDefault { value_type, .. } => format!("not a valid {}", value_type),
+            ParseError::PatternDefault { .. } => "default for a pattern".to_string(),
+        }
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            ParseError::InvalidLine { .. } => {
+                Some("comment lines start with `#` or `;`".to_string())
+            }
+            ParseError::InvalidType { .. } => {
+                Some("expected one of `string`, `bool`, `integer`".to_string())
+            }
+            ParseError::DuplicateKey { first_line, .. } => {
+                Some(format!("first defined at line {}", first_line))
+            }
+            ParseError::ConflictingPatterns { other_line, .. } => Some(format!(
+                "make one pattern more specific than the other (line {}), or give both the same type",
+                other_line
+            )),
+            ParseError::UnterminatedQuote { .. } => {
+                Some("close the value with the same quote it starts with".to_string())
+            }
+            ParseError::InvalidEscape { .. } => Some(
+                "supported escapes are `\\n`, `\\t`, `\\\\`, `\\\"`, `\\'` and `\\u{...}`"
+                    .to_string(),
+            ),
+            ParseError::InvalidKey { .. } => Some(
+                "keys are segments of letters, digits, `_`, `-` and glob characters separated by \
+                 `.` or `/`; use `KeyGrammar::allow` for other characters"
+                    .to_string(),
+            ),
+            ParseError::InvalidDefault { value_type, .. } => type_help(&value_type.to_string()),
+            ParseError::PatternDefault { .. } => Some(
+                "write `type?` to make matching keys optional, or give each key its own line"
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+impl Diagnostic for ParseWarning {
+    fn message(&self) -> String {
+        match self {
+            ParseWarning::DuplicateKey { key, .. } => {
+                format!("'{}' is defined more than once", key)
+            }
+        }
+    }
+
+    fn span(&self) -> Option<Span> {
+        match self {
+            ParseWarning::DuplicateKey { span, .. } => Some(*span),
+        }
+    }
+
+    fn label(&self) -> String {
+        "defined again here".to_string()
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            ParseWarning::DuplicateKey { first_line, .. } => {
+                Some(format!("first defined at line {}", first_line))
+            }
+        }
+    }
+}
+
+// MissingKey の span はスキーマファイルを指すので、source にはスキーマの内容を渡すこと
+impl Diagnostic for ValidationError {
+    fn message(&self) -> String {
+        match self {
+            ValidationError::TypeMismatch {
+                key, expected, got, ..
+            } => {
+                format!("'{}' expects {}, got '{}'", key, expected, got)
+            }
+            ValidationError::UnknownKey { key, .. } => format!("unknown key '{}'", key),
+            ValidationError::MissingKey { key, .. } => format!("missing key '{}'", key),
+            ValidationError::ProbableTypo {
+                key, schema_key, ..
+            } => {
+                format!("unknown key '{}'; did you mean '{}'?", key, schema_key)
+            }
+        }
+    }
+
+    fn span(&self) -> Option<Span> {
+        match self {
+            ValidationError::TypeMismatch { span, .. }
+            | ValidationError::UnknownKey { span, .. }
+            | ValidationError::MissingKey { span, .. }
+            | ValidationError::ProbableTypo { span, .. } => Some(*span),
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            ValidationError::TypeMismatch { expected, .. } => format!("not a valid {}", expected),
+            ValidationError::UnknownKey { .. } => "not in schema".to_string(),
+            ValidationError::MissingKey { .. } => "required by schema".to_string(),
+            ValidationError::ProbableTypo { .. } => "probable typo".to_string(),
+        }
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            ValidationError::TypeMismatch { expected, .. } => type_help(expected),
+            ValidationError::UnknownKey { suggestions, .. } if !suggestions.is_empty() => {
+                let quoted: Vec<String> = suggestions.iter().map(|s| format!("'{}'", s)).collect();
+                Some(format!("did you mean {}?", quoted.join(" or ")))
+            }
+            ValidationError::UnknownKey { .. } => Some(
+                "add the key to the schema, or prefix the line with `-` to only warn".to_string(),
+            ),
+            ValidationError::ProbableTypo {
+                schema_key,
+                schema_span,
+                ..
+            } => Some(format!(
+                "'{}' is required by schema (schema line {}) but missing",
+                schema_key, schema_span.line
+            )),
+            ValidationError::MissingKey { key, .. } => {
+                Some(format!("add `{} = ...` to the config", key))
+            }
+        }
+    }
+}
+
+impl Diagnostic for InterpolationError {
+    fn message(&self) -> String {
+        match self {
+            InterpolationError::Undefined { name, .. } => format!("undefined variable '{}'", name),
+            InterpolationError::Cycle { key, .. } => format!("'{}' refers to itself", key),
+            InterpolationError::Unterminated { .. } => "unterminated '${'".to_string(),
+        }
+    }
+
+    fn span(&self) -> Option<Span> {
+        match self {
+            InterpolationError::Undefined { span, .. }
+            | InterpolationError::Cycle { span, .. }
+            | InterpolationError::Unterminated { span, .. } => Some(*span),
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            InterpolationError::Undefined { .. } => "not a key or variable".to_string(),
+            InterpolationError::Cycle { .. } => "completes a cycle".to_string(),
+            InterpolationError::Unterminated { .. } => "missing `}`".to_string(),
+        }
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            InterpolationError::Undefined { .. } => {
+                Some("write `$$` for a literal `$`".to_string())
+            }
+            InterpolationError::Cycle { chain, .. } => {
+                Some(format!("reference chain: {}", chain.join(" -> ")))
+            }
+            InterpolationError::Unterminated { .. } => None,
+        }
+    }
+}
+
+// Missing は位置がないので該当行を表示しない
+impl Diagnostic for GetError {
+    fn message(&self) -> String {
+        match self {
+            GetError::Missing { key } => format!("missing key '{}'", key),
+            GetError::Invalid { key, expected, .. } => {
+                format!("'{}' is not a valid {}", key, expected)
+            }
+            GetError::OutOfRange { key, expected, .. } => {
+                format!("'{}' is out of range for {}", key, expected)
+            }
+        }
+    }
+
+    fn span(&self) -> Option<Span> {
+        match self {
+            GetError::Missing { .. } => None,
+            GetError::Invalid { span, .. } | GetError::OutOfRange { span, .. } => Some(*span),
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            GetError::Missing { .. } => String::new(),
+            GetError::Invalid { expected, .. } => format!("not a valid {}", expected),
+            GetError::OutOfRange { .. } => "out of range".to_string(),
+        }
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            GetError::Missing { key } => Some(format!("add `{} = ...` to the config", key)),
+            GetError::Invalid { expected, .. } | GetError::OutOfRange { expected, .. } => {
+                type_help(expected)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Diagnostic for DeError {
+    fn message(&self) -> String {
+        match self {
+            DeError::Value(error) => error.message(),
+            DeError::Custom { message, .. } => message.clone(),
+        }
+    }
+
+    fn span(&self) -> Option<Span> {
+        match self {
+            DeError::Value(error) => error.span(),
+            DeError::Custom { span, .. } => *span,
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            DeError::Value(error) => error.label(),
+            DeError::Custom { .. } => "while reading this value".to_string(),
+        }
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            DeError::Value(error) => error.help(),
+            DeError::Custom { .. } => None,
+        }
+    }
+}
+
+fn type_help(expected: &str) -> Option<String> {
+    match expected {
+        "bool" => Some("expected bool: `true` or `false`".to_string()),
+        "integer" => {
+            Some("expected integer: a 64-bit signed integer such as `3` or `-1`".to_string())
+        }
+        "u64" => Some("expected u64: a 64-bit unsigned integer such as `3`".to_string()),
+        _ => None,
+    }
+}
+
+// === Renderer ===
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    // 書き込み先が端末のときだけ色を付ける
+    Auto,
+    Always,
+    #[default]
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    file_name: String,
+    color: ColorChoice,
+}
+
+impl Renderer {
+    pub fn new(file_name: &str) -> Self {
+        Renderer {
+            file_name: file_name.to_string(),
+            color: ColorChoice::default(),
+        }
+    }
+
+    pub fn color(mut self, color: ColorChoice) -> Self {
+        self.color = color;
+        self
+    }
+
+    // 文字列には端末かどうかが分からないので、Auto は色なしとして扱う
+    pub fn render(&self, diagnostic: &dyn Diagnostic, source: &str) -> String {
+        self.render_level(
+            Level::Error,
+            diagnostic,
+            source,
+            self.color == ColorChoice::Always,
+        )
+    }
+
+    pub fn render_warning(&self, diagnostic: &dyn Diagnostic, source: &str) -> String {
+        self.render_level(
+            Level::Warning,
+            diagnostic,
+            source,
+            self.color == ColorChoice::Always,
+        )
+    }
+
+    pub fn emit<W: Write + IsTerminal>(
+        &self,
+        out: &mut W,
+        diagnostic: &dyn Diagnostic,
+        source: &str,
+    ) -> io::Result<()> {
+        self.emit_level(Level::Error, out, diagnostic, source)
+    }
+
+    pub fn emit_warning<W: Write + IsTerminal>(
+        &self,
+        out: &mut W,
+        diagnostic: &dyn Diagnostic,
+        source: &str,
+    ) -> io::Result<()> {
+        self.emit_level(Level::Warning, out, diagnostic, source)
+    }
+
+    fn emit_level<W: Write + IsTerminal>(
+        &self,
+        level: Level,
+        out: &mut W,
+        diagnostic: &dyn Diagnostic,
+        source: &str,
+    ) -> io::Result<()> {
+        let color = match self.color {
+            ColorChoice::Auto => out.is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        };
+        out.write_all(
+            self.render_level(level, diagnostic, source, color)
+                .as_bytes(),
+        )
+    }
+
+    fn render_level(
+        &self,
+        level: Level,
+        diagnostic: &dyn Diagnostic,
+        source: &str,
+        color: bool,
+    ) -> String {
+        let paint = |code: &str, text: &str| {
+            if color {
+                format!("\x1b[{}m{}\x1b[0m", code, text)
+            } else {
+                text.to_string()
+            }
+        };
+        let (name, level_color) = match level {
+            Level::Error => ("error", "1;31"),
+            Level::Warning => ("warning", "1;33"),
+        };
+
+        let mut out = format!(
+            "{}{}\n",
+            paint(level_color, name),
+            paint("1", &format!(": {}", diagnostic.message()))
+        );
+
+        let snippet = diagnostic
+            .span()
+            .filter(|span| span.line > 0 && span.start <= span.end && span.end <= source.len());
+        let Some(span) = snippet else {
+            out.push_str(&format!(" {} {}\n", paint("1;34", "-->"), self.file_name));
+            if let Some(help) = diagnostic.help() {
+                out.push_str(&format!(" {} help: {}\n", paint("1;34", "="), help));
+            }
+            return out;
+        };
+
+        let line_start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[span.start..]
+            .find('\n')
+            .map_or(source.len(), |i| span.start + i);
+        let line = source[line_start..line_end].trim_end_matches('\r');
+        let gutter = " ".repeat(span.line.to_string().len());
+        let bar = paint("1;34", "|");
+
+        // 行末の \r を指す span は、除いた後の行末を指すものとして扱う
+        let start = span.start.min(line_start + line.len());
+        // タブはそのまま残して下線の位置を合わせる
+        let indent: String = line[..start - line_start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        // 複数行にまたがる span は最初の行の終わりまで下線を引く
+        let width = source[start..span.end.min(line_start + line.len()).max(start)]
+            .chars()
+            .count()
+            .max(1);
+
+        out.push_str(&format!(
+            "{}{} {}:{}:{}\n",
+            gutter,
+            paint("1;34", "-->"),
+            self.file_name,
+            span.line,
+            span.column
+        ));
+        out.push_str(&format!("{} {}\n", gutter, bar));
+        out.push_str(&format!(
+            "{} {} {}\n",
+            paint("1;34", &span.line.to_string()),
+            bar,
+            line
+        ));
+        out.push_str(&format!(
+            "{} {} {}{}\n",
+            gutter,
+            bar,
+            indent,
+            paint(
+                level_color,
+                &format!("{} {}", "^".repeat(width), diagnostic.label())
+            )
+        ));
+        if let Some(help) = diagnostic.help() {
+            out.push_str(&format!("{} {}\n", gutter, bar));
+            out.push_str(&format!(
+                "{} {} help: {}\n",
+                gutter,
+                paint("1;34", "="),
+                help
+            ));
+        }
+        out
+    }
+}
+
+// === テスト ===
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, Schema, validate};
+
+    #[test]
+    fn type_mismatch_is_rendered_with_snippet_and_help() {
+        let source = "# retry\nretry = abc\n";
+        let config = Config::parse(source).unwrap();
+        let schema = Schema::parse("retry = bool").unwrap();
+        let errors = validate(&config, &schema).unwrap_err();
+        let rendered = Renderer::new("sysctl.conf").render(&errors[0], source);
+        assert_eq!(
+            rendered,
+            "\
+error: 'retry' expects bool, got 'abc'
+ --> sysctl.conf:2:9
+  |
+2 | retry = abc
+  |         ^^^ not a valid bool
+  |
+  = help: expected bool: `true` or `false`
+"
+        );
+    }
+
+    #[test]
+    fn parse_error_underlines_the_whole_line() {
+        let source = "debug = true\n\tnot a pair\n";
+        let err = Config::parse(source).unwrap_err();
+        let rendered = Renderer::new("a.conf").render(&err, source);
+        assert!(rendered.starts_with("error: invalid syntax\n --> a.conf:2:2\n"));
+        assert!(rendered.contains("2 | \tnot a pair\n  | \t^^^^^^^^^^ expected `key = value`\n"));
+    }
+
+    #[test]
+    fn span_after_a_trailing_carriage_return_is_clamped() {
+        let source = "retry =\r";
+        let err = Schema::parse(source).unwrap_err();
+        let rendered = Renderer::new("s").render(&err, source);
+        assert!(rendered.contains("1 | retry =\n  |        ^"));
+
+        let config = Config::parse(source).unwrap();
+        let schema = Schema::parse("retry = integer").unwrap();
+        let errors = validate(&config, &schema).unwrap_err();
+        let rendered = Renderer::new("a.conf").render(&errors[0], source);
+        assert!(rendered.contains("1 | retry =\n  |        ^"));
+    }
+
+    #[test]
+    fn invalid_key_help_matches_the_default_grammar() {
+        let source = "a b = 1";
+        let err = Config::parse(source).unwrap_err();
+        let rendered = Renderer::new("a.conf").render(&err, source);
+        assert!(rendered.contains(
+            "= help: keys are segments of letters, digits, `_`, `-` and glob characters"
+        ));
+        // ヘルプに挙げた文字は使える
+        assert!(Config::parse("net.ipv4.conf.br-lan_0.rp_filter = 1").is_ok());
+    }
+
+    #[test]
+    fn color_is_only_used_when_requested() {
+        let source = "retry = abc";
+        let err = Schema::parse(source).unwrap_err();
+        assert!(!Renderer::new("s").render(&err, source).contains('\x1b'));
+        // 文字列への出力は端末ではないので Auto でも色は付かない
+        let auto = Renderer::new("s").color(ColorChoice::Auto);
+        assert!(!auto.render(&err, source).contains('\x1b'));
+        let always = Renderer::new("s").color(ColorChoice::Always);
+        assert!(always.render(&err, source).contains("\x1b[1;31merror"));
+    }
+}
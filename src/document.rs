@@ -0,0 +1,687 @@
+use std::fmt;
+
+use crate::quote::{needs_quotes, quote};
+use crate::{
+    Config, DuplicatePolicy, ParseError, ParseOptions, Token, kv_layout, normalize_key,
+    split_entries, tokenize_line,
+};
+
+// === エラー型 ===
+
+#[derive(Debug)]
+pub enum EditError {
+    KeyNotFound { key: String },
+    KeyExists { key: String },
+    InvalidEntry { key: String, value: String },
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::KeyNotFound { key } => write!(f, "'{}': no such key", key),
+            EditError::KeyExists { key } => write!(f, "'{}': key already exists", key),
+            EditError::InvalidEntry { key, value } => {
+                write!(f, "'{}': cannot be written as a line: '{}'", key, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+// === Document ===
+//
+// コメント・空行・空白・改行コードを含めて元のファイルをそのまま保持する。
+// 変更しなければ to_string() でパース前と同じバイト列に戻る。
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    // 改行コードを除いた行の原文。継続行（行末の \）はまとめて1つの Line になり、途中の改行を含む
+    raw: String,
+    // "\n" / "\r\n"。末尾に改行のない最終行では ""
+    newline: String,
+    token: Token,
+}
+
+impl Line {
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn newline(&self) -> &str {
+        &self.newline
+    }
+
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Document {
+    lines: Vec<Line>,
+    // 編集した行を読み直すときにも同じ設定を使う
+    options: ParseOptions,
+}
+
+impl Document {
+    pub fn parse(content: &str) -> Result<Self, ParseError> {
+        Document::parse_with(content, &ParseOptions::default())
+    }
+
+    pub fn parse_with(content: &str, options: &ParseOptions) -> Result<Self, ParseError> {
+        let lines = split_entries(content, options)
+            .into_iter()
+            .map(|entry| {
+                let token = tokenize_line(entry.raw, entry.line_number, entry.offset, options)?;
+                Ok(Line {
+                    raw: entry.raw.to_string(),
+                    newline: entry.newline.to_string(),
+                    token,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Document {
+            lines,
+            options: options.clone(),
+        })
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    // 編集はどれも読み直して確かめているが、エラーは握りつぶさずに返す
+    pub fn to_config(&self) -> Result<Config, ParseError> {
+        let options = ParseOptions {
+            duplicates: DuplicatePolicy::default(),
+            ..self.options.clone()
+        };
+        self.to_config_with(&options)
+    }
+
+    pub fn to_config_with(&self, options: &ParseOptions) -> Result<Config, ParseError> {
+        // 編集で行の位置がずれているので、span は書き出した内容から計算し直す
+        Config::parse_with(&self.to_string(), options)
+    }
+
+    // 同じキーが複数あるときは Config と同じく最後の定義が有効
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.position(key).map(|i| match &self.lines[i].token {
+            Token::KeyValue { value, .. } => value.as_str(),
+            _ => unreachable!(),
+        })
+    }
+
+    // === 編集 ===
+    //
+    // どの操作も対象の行だけを書き換え、それ以外の行は1バイトも変えない。
+
+    // 既存のキーなら値の部分だけを置き換える。
+    // 新しいキーはドット区切りの接頭辞を最も多く共有する行の後ろに置く（なければ末尾）。
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), EditError> {
+        match self.position(key) {
+            Some(i) => {
+                let line = &self.lines[i];
+                let layout = kv_layout(&line.raw).unwrap();
+                let mut text = self.written(value);
+                // "key =" のように値が空だった行は = の前の空白に合わせる
+                if layout.value.start == layout.eq + 1
+                    && layout.value.is_empty()
+                    && layout.key.end < layout.eq
+                {
+                    text.insert(0, ' ');
+                }
+                let mut raw = line.raw.clone();
+                raw.replace_range(layout.value, &text);
+                self.replace_raw(i, raw, key, value)
+            }
+            None => {
+                let index = self
+                    .sibling_position(key)
+                    .map_or(self.lines.len(), |i| i + 1);
+                self.insert_at(index, key, value)
+            }
+        }
+    }
+
+    pub fn insert_after(&mut self, anchor: &str, key: &str, value: &str) -> Result<(), EditError> {
+        let i = self.position_for_insert(anchor, key)?;
+        self.insert_at(i + 1, key, value)
+    }
+
+    pub fn insert_before(&mut self, anchor: &str, key: &str, value: &str) -> Result<(), EditError> {
+        let i = self.position_for_insert(anchor, key)?;
+        self.insert_at(i, key, value)
+    }
+
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<(), EditError> {
+        if self.position(to).is_some() {
+            return Err(EditError::KeyExists {
+                key: to.to_string(),
+            });
+        }
+        let i = self.require(from)?;
+        let line = &self.lines[i];
+        let layout = kv_layout(&line.raw).unwrap();
+        let value = match &line.token {
+            Token::KeyValue { value, .. } => value.clone(),
+            _ => unreachable!(),
+        };
+        let mut raw = line.raw.clone();
+        raw.replace_range(layout.key, to);
+        self.replace_raw(i, raw, to, &value)
+    }
+
+    // 同じキーの定義がすべて消える
+    pub fn remove(&mut self, key: &str) -> Result<(), EditError> {
+        self.require(key)?;
+        let last = self.lines.len() - 1;
+        let removed_last = self.positions(key).contains(&last);
+        self.lines.retain(|line| !defines(line, key));
+        // 末尾に改行のない最終行を消したら、新しい最終行もそれに合わせる
+        if removed_last && let Some(line) = self.lines.last_mut() {
+            line.newline.clear();
+        }
+        Ok(())
+    }
+
+    // 同じキーの定義をすべて "# " でコメントアウトする（インデントは保つ）。
+    // 継続行は物理行ごとにコメントにする
+    pub fn comment_out(&mut self, key: &str) -> Result<(), EditError> {
+        self.require(key)?;
+        self.lines = std::mem::take(&mut self.lines)
+            .into_iter()
+            .flat_map(|line| {
+                if defines(&line, key) {
+                    commented(line)
+                } else {
+                    vec![line]
+                }
+            })
+            .collect();
+        Ok(())
+    }
+
+    // "# key = value" の形をした最後のコメント行を有効な行に戻す
+    pub fn uncomment(&mut self, key: &str) -> Result<(), EditError> {
+        if self.position(key).is_some() {
+            return Err(EditError::KeyExists {
+                key: key.to_string(),
+            });
+        }
+        let (i, raw, token) = self
+            .lines
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(i, line)| {
+                let raw = uncommented(line)?;
+                match tokenize_line(&raw, i + 1, 0, &self.options) {
+                    Ok(token @ Token::KeyValue { .. }) if defines_token(&token, key) => {
+                        Some((i, raw, token))
+                    }
+                    _ => None,
+                }
+            })
+            .ok_or_else(|| EditError::KeyNotFound {
+                key: key.to_string(),
+            })?;
+        // 行末が \ なら、戻した行が次の行の続きとして読まれる
+        if let Some(next) = self.lines.get(i + 1) {
+            let joined = format!("{}{}{}", raw, self.lines[i].newline, next.raw);
+            if split_entries(&joined, &self.options).len() != 2 {
+                let Token::KeyValue { value, .. } = token else {
+                    unreachable!()
+                };
+                return Err(EditError::InvalidEntry {
+                    key: key.to_string(),
+                    value,
+                });
+            }
+        }
+        self.lines[i].token = token;
+        self.lines[i].raw = raw;
+        Ok(())
+    }
+
+    // 先頭の - (ignore_error) を付け外しする
+    pub fn set_ignore_error(&mut self, key: &str, ignore_error: bool) -> Result<(), EditError> {
+        let i = self.require(key)?;
+        let line = &mut self.lines[i];
+        let layout = kv_layout(&line.raw).unwrap();
+        match (layout.dash, ignore_error) {
+            (None, true) => line.raw.insert(layout.key.start, '-'),
+            (Some(dash), false) => {
+                line.raw.remove(dash);
+            }
+            _ => {}
+        }
+        line.token = tokenize_line(&line.raw, i + 1, 0, &self.options).unwrap();
+        Ok(())
+    }
+
+    fn positions(&self, key: &str) -> Vec<usize> {
+        (0..self.lines.len())
+            .filter(|&i| defines(&self.lines[i], key))
+            .collect()
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        self.positions(key).pop()
+    }
+
+    fn require(&self, key: &str) -> Result<usize, EditError> {
+        self.position(key).ok_or_else(|| EditError::KeyNotFound {
+            key: key.to_string(),
+        })
+    }
+
+    fn position_for_insert(&self, anchor: &str, key: &str) -> Result<usize, EditError> {
+        if self.position(key).is_some() {
+            return Err(EditError::KeyExists {
+                key: key.to_string(),
+            });
+        }
+        self.require(anchor)
+    }
+
+    // ドット区切りの先頭セグメントを最も多く共有する行（同点なら後ろの行）
+    fn sibling_position(&self, key: &str) -> Option<usize> {
+        let key = normalize_key(key);
+        self.lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| match &line.token {
+                Token::KeyValue { key: k, .. } => Some((common_segments(k, &key), i)),
+                _ => None,
+            })
+            .filter(|&(shared, _)| shared > 0)
+            .max()
+            .map(|(_, i)| i)
+    }
+
+    fn insert_at(&mut self, index: usize, key: &str, value: &str) -> Result<(), EditError> {
+        let raw = format!("{} = {}", key, self.written(value));
+        let token = self.checked_token(&raw, key, value)?;
+        let newline = self.default_newline().to_string();
+        let mut line = Line {
+            raw,
+            newline,
+            token,
+        };
+        if index == self.lines.len() {
+            // 末尾に改行のない最終行の後ろに追加する場合は、改行の有無を引き継ぐ
+            if let Some(last) = self.lines.last_mut()
+                && last.newline.is_empty()
+            {
+                // 最終行が \ で終わっていると、追加した行がその続きとして読まれる
+                let joined = format!("{}{}{}", last.raw, line.newline, line.raw);
+                if split_entries(&joined, &self.options).len() != 2 {
+                    return Err(EditError::InvalidEntry {
+                        key: key.to_string(),
+                        value: value.to_string(),
+                    });
+                }
+                std::mem::swap(&mut last.newline, &mut line.newline);
+            }
+        }
+        self.lines.insert(index, line);
+        Ok(())
+    }
+
+    fn replace_raw(
+        &mut self,
+        i: usize,
+        raw: String,
+        key: &str,
+        value: &str,
+    ) -> Result<(), EditError> {
+        self.lines[i].token = self.checked_token(&raw, key, value)?;
+        self.lines[i].raw = raw;
+        Ok(())
+    }
+
+    // 引用符が使えるなら、そのままでは書けない値を引用符で囲む
+    fn written(&self, value: &str) -> String {
+        if self.options.quoted_values && needs_quotes(value) {
+            quote(value)
+        } else {
+            value.to_string()
+        }
+    }
+
+    // 書き換えた行がパースし直しても同じキーと値になることを確かめる
+    fn checked_token(&self, raw: &str, key: &str, value: &str) -> Result<Token, EditError> {
+        let token = tokenize_line(raw, 0, 0, &self.options)
+            .ok()
+            .filter(|token| {
+                !key.contains(['\n', '\r'])
+                    && !self.written(value).contains(['\n', '\r'])
+                    && matches!(token, Token::KeyValue { key: k, value: v, .. }
+                    if *k == normalize_key(key) && v == value)
+            });
+        token.ok_or_else(|| EditError::InvalidEntry {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+    }
+
+    fn default_newline(&self) -> &str {
+        self.lines
+            .iter()
+            .map(|line| line.newline.as_str())
+            .find(|newline| !newline.is_empty())
+            .unwrap_or("\n")
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            write!(f, "{}{}", line.raw, line.newline)?;
+        }
+        Ok(())
+    }
+}
+
+fn defines(line: &Line, key: &str) -> bool {
+    defines_token(&line.token, key)
+}
+
+fn defines_token(token: &Token, key: &str) -> bool {
+    matches!(token, Token::KeyValue { key: k, .. } if *k == normalize_key(key))
+}
+
+fn common_segments(a: &str, b: &str) -> usize {
+    a.split('.')
+        .zip(b.split('.'))
+        .take_while(|(x, y)| x == y)
+        .count()
+}
+
+fn commented(line: Line) -> Vec<Line> {
+    let count = line.raw.split('\n').count();
+    line.raw
+        .split('\n')
+        .enumerate()
+        .map(|(i, physical)| {
+            let (physical, newline) = match physical.strip_suffix('\r') {
+                Some(physical) => (physical, "\r\n"),
+                None => (physical, "\n"),
+            };
+            let indent = physical.len() - physical.trim_start().len();
+            let mut raw = physical.to_string();
+            raw.insert_str(indent, "# ");
+            Line {
+                token: Token::Comment(raw.trim().to_string()),
+                raw,
+                newline: if i + 1 == count {
+                    line.newline.clone()
+                } else {
+                    newline.to_string()
+                },
+            }
+        })
+        .collect()
+}
+
+// コメント記号とその直後の空白を取り除いた行（インデントは残す）
+fn uncommented(line: &Line) -> Option<String> {
+    if !matches!(line.token, Token::Comment(_)) {
+        return None;
+    }
+    let indent = line.raw.len() - line.raw.trim_start().len();
+    let body = line.raw[indent..][1..].trim_start();
+    Some(format!("{}{}", &line.raw[..indent], body))
+}
+
+// === テスト ===
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::arb_config_content;
+    use proptest::prelude::*;
+
+    // 行頭・= の前後・行末の空白や改行コードを崩した設定ファイル
+    fn arb_messy_content() -> impl Strategy<Value = String> {
+        (
+            arb_config_content(),
+            "[ \t]{0,3}",
+            prop_oneof![Just("\n"), Just("\r\n")],
+            any::<bool>(),
+        )
+            .prop_map(|(content, pad, newline, trailing)| {
+                let mut out = content
+                    .lines()
+                    .map(|line| format!("{}{}{}", pad, line.replacen('=', "\t=  ", 1), pad))
+                    .collect::<Vec<_>>()
+                    .join(newline);
+                if trailing {
+                    out.push_str(newline);
+                }
+                out
+            })
+    }
+
+    // --- ラウンドトリップ: 無変更ならバイト単位で一致する ---
+
+    proptest! {
+        #[test]
+        fn unmodified_document_round_trips(content in arb_messy_content()) {
+            let doc = Document::parse(&content).unwrap();
+            prop_assert_eq!(doc.to_string(), content);
+        }
+
+        #[test]
+        fn document_and_config_agree(content in arb_config_content()) {
+            // Document 経由でも Config::parse と同じ値が得られる
+            let from_doc = Document::parse(&content).unwrap().to_config().unwrap();
+            let direct = Config::parse(&content).unwrap();
+            prop_assert_eq!(from_doc.entries, direct.entries);
+        }
+    }
+
+    #[test]
+    fn document_keeps_comments_and_ignore_error_prefix() {
+        let content = "# net\r\n\r\n  -net.ipv4.conf.default.rp_filter=1 \r\n";
+        let doc = Document::parse(content).unwrap();
+        assert_eq!(doc.lines().len(), 3);
+        assert_eq!(doc.lines()[0].token(), &Token::Comment("# net".to_string()));
+        assert_eq!(
+            doc.lines()[2].raw(),
+            "  -net.ipv4.conf.default.rp_filter=1 "
+        );
+        assert!(matches!(
+            doc.lines()[2].token(),
+            Token::KeyValue {
+                ignore_error: true,
+                ..
+            }
+        ));
+        assert_eq!(doc.to_string(), content);
+    }
+
+    // --- 編集: 対象の行以外は変わらない ---
+
+    proptest! {
+        #[test]
+        fn set_existing_key_only_touches_that_line(content in arb_messy_content()) {
+            let mut doc = Document::parse(&content).unwrap();
+            let Some(key) = doc.lines().iter().find_map(|line| match line.token() {
+                Token::KeyValue { key, .. } if !key.is_empty() => Some(key.clone()),
+                _ => None,
+            }) else {
+                return Ok(());
+            };
+            let before = doc.clone();
+            doc.set(&key, "changed").unwrap();
+            prop_assert_eq!(doc.get(&key), Some("changed"));
+            let changed = before.lines().iter().zip(doc.lines()).filter(|(a, b)| a != b).count();
+            prop_assert!(changed <= 1);
+        }
+    }
+
+    #[test]
+    fn set_keeps_spacing_and_comments() {
+        let mut doc = Document::parse(
+            "\
+# endpoint
+endpoint   =\tlocalhost:3000   # not a comment
+debug = true
+",
+        )
+        .unwrap();
+        doc.set("endpoint", "example.com:80").unwrap();
+        assert_eq!(
+            doc.to_string(),
+            "\
+# endpoint
+endpoint   =\texample.com:80
+debug = true
+"
+        );
+    }
+
+    #[test]
+    fn new_key_is_placed_next_to_siblings() {
+        let mut doc = Document::parse(
+            "\
+endpoint = localhost:3000
+log.file = /var/log/console.log
+
+debug = true",
+        )
+        .unwrap();
+        doc.set("log.level", "info").unwrap();
+        doc.set("retry", "3").unwrap();
+        assert_eq!(
+            doc.to_string(),
+            "\
+endpoint = localhost:3000
+log.file = /var/log/console.log
+log.level = info
+
+debug = true
+retry = 3"
+        );
+    }
+
+    #[test]
+    fn comment_out_uncomment_and_ignore_error_rewrite_one_line() {
+        let mut doc =
+            Document::parse("  net.ipv4.ip_forward = 1\n# debug = true\nlog.file = a").unwrap();
+        doc.comment_out("net.ipv4.ip_forward").unwrap();
+        doc.uncomment("debug").unwrap();
+        doc.set_ignore_error("log.file", true).unwrap();
+        doc.rename("log.file", "log.path").unwrap();
+        assert_eq!(
+            doc.to_string(),
+            "  # net.ipv4.ip_forward = 1\ndebug = true\n-log.path = a"
+        );
+        assert_eq!(doc.get("net.ipv4.ip_forward"), None);
+
+        doc.remove("log.path").unwrap();
+        assert_eq!(doc.to_string(), "  # net.ipv4.ip_forward = 1\ndebug = true");
+    }
+
+    #[test]
+    fn edits_that_would_not_round_trip_are_rejected() {
+        let mut doc = Document::parse("debug = true").unwrap();
+        assert!(matches!(
+            doc.set("debug", "a\nb = c"),
+            Err(EditError::InvalidEntry { .. })
+        ));
+        assert!(matches!(
+            doc.insert_after("debug", "debug", "x"),
+            Err(EditError::KeyExists { .. })
+        ));
+        assert!(matches!(
+            doc.remove("retry"),
+            Err(EditError::KeyNotFound { .. })
+        ));
+        assert_eq!(doc.to_string(), "debug = true");
+    }
+
+    #[test]
+    fn slash_spelling_is_kept_when_editing() {
+        let mut doc = Document::parse("net/ipv4/ip_forward = 0\n").unwrap();
+        doc.set("net.ipv4.ip_forward", "1").unwrap();
+        doc.set("net/ipv4/tcp_syncookies", "1").unwrap();
+        assert_eq!(
+            doc.to_string(),
+            "net/ipv4/ip_forward = 1\nnet/ipv4/tcp_syncookies = 1\n"
+        );
+        assert_eq!(doc.get("net.ipv4.tcp_syncookies"), Some("1"));
+    }
+
+    #[test]
+    fn values_are_quoted_when_needed() {
+        let options = ParseOptions {
+            quoted_values: true,
+            ..ParseOptions::default()
+        };
+        let mut doc = Document::parse_with("motd = \"hi\"\n", &options).unwrap();
+        assert_eq!(doc.get("motd"), Some("hi"));
+        doc.set("motd", "line1\nline2").unwrap();
+        doc.set("banner", "  padded ").unwrap();
+        doc.set("debug", "true").unwrap();
+        assert_eq!(
+            doc.to_string(),
+            "motd = \"line1\\nline2\"\nbanner = \"  padded \"\ndebug = true\n"
+        );
+        assert_eq!(doc.to_config().unwrap().get("banner"), Some("  padded "));
+    }
+
+    #[test]
+    fn continued_lines_are_kept_and_edited_as_one_entry() {
+        let options = ParseOptions {
+            line_continuation: true,
+            ..ParseOptions::default()
+        };
+        let content = "ports = 1024-2048 \\\r\n  4096-8192\r\ndebug = true\r\n";
+        let mut doc = Document::parse_with(content, &options).unwrap();
+        assert_eq!(doc.to_string(), content);
+        assert_eq!(doc.lines().len(), 2);
+        assert_eq!(doc.get("ports"), Some("1024-2048 4096-8192"));
+
+        doc.comment_out("ports").unwrap();
+        assert_eq!(
+            doc.to_string(),
+            "# ports = 1024-2048 \\\r\n  # 4096-8192\r\ndebug = true\r\n"
+        );
+        assert_eq!(doc.to_config().unwrap().get("ports"), None);
+
+        // \ で終わる最終行の後ろには追加できない
+        let mut doc = Document::parse_with("a = b \\", &options).unwrap();
+        assert!(matches!(
+            doc.set("c", "d"),
+            Err(EditError::InvalidEntry { .. })
+        ));
+        assert_eq!(doc.to_string(), "a = b \\");
+        assert_eq!(doc.get("c"), None);
+        let mut doc = Document::parse_with("a = b", &options).unwrap();
+        doc.set("c", "d").unwrap();
+        assert_eq!(doc.to_config().unwrap().get("c"), Some("d"));
+
+        // \ で終わる行は、次の行とつながるならコメントから戻せない
+        let mut doc = Document::parse_with("# a = 1 \\\nb = 2\n", &options).unwrap();
+        assert!(matches!(
+            doc.uncomment("a"),
+            Err(EditError::InvalidEntry { .. })
+        ));
+        assert_eq!(doc.to_string(), "# a = 1 \\\nb = 2\n");
+        assert_eq!(doc.to_config().unwrap().get("b"), Some("2"));
+        let options = ParseOptions {
+            quoted_values: true,
+            ..options
+        };
+        let mut doc = Document::parse_with("# a = \"x\" \\\nb = 1\n", &options).unwrap();
+        assert!(doc.uncomment("a").is_err());
+        assert!(doc.to_config().is_ok());
+        let mut doc = Document::parse_with("# a = 1 \\", &options).unwrap();
+        doc.uncomment("a").unwrap();
+        assert_eq!(doc.to_config().unwrap().get("a"), doc.get("a"));
+    }
+}
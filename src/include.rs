@@ -0,0 +1,208 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{Config, LoadError, ParseOptions, Token, pattern, tokenize};
+
+// === include ===
+//
+// ParseOptions::includes で選んだ書き方の行を見つけたら、その位置に別のファイルのエントリを差し込む。
+// 重複の扱いは1つのファイルに書いたときと同じ（DuplicatePolicy に従う）。
+//
+//   .include conf.d/*.conf
+//   include = /etc/sysctl.d/common.conf
+//
+// 相対パスは include を書いたファイルのディレクトリから探す。
+// ファイル名にはグロブが使え、マッチしたファイルを名前順に読む（1つもなくてもよい）。
+
+impl Config {
+    // path を読み、include を辿って1つの Config にする
+    pub fn from_file(path: impl AsRef<Path>, options: &ParseOptions) -> Result<Config, LoadError> {
+        load(Path::new("/"), path.as_ref(), options)
+    }
+}
+
+// 絶対パスの include は root の下にあるものとして扱う（Loader::root と同じ）
+pub(crate) fn load(root: &Path, path: &Path, options: &ParseOptions) -> Result<Config, LoadError> {
+    let mut config = Config::default();
+    splice(&mut config, root, path, options, &mut Vec::new())?;
+    Ok(config)
+}
+
+// stack は読み込み中のファイル（循環の検出用）
+fn splice(
+    config: &mut Config,
+    root: &Path,
+    path: &Path,
+    options: &ParseOptions,
+    stack: &mut Vec<PathBuf>,
+) -> Result<(), LoadError> {
+    let io = |error| LoadError::Io {
+        path: path.to_path_buf(),
+        error,
+    };
+    let parse = |error| LoadError::Parse {
+        path: path.to_path_buf(),
+        error,
+    };
+    let content = fs::read_to_string(path).map_err(io)?;
+    stack.push(fs::canonicalize(path).map_err(io)?);
+
+    let mut pending = Vec::new();
+    for token in tokenize(&content, options).map_err(parse)? {
+        let Token::Include { path: target, span } = token else {
+            pending.push(token);
+            continue;
+        };
+        config
+            .absorb(pending.drain(..), options, Some(path))
+            .map_err(parse)?;
+        let included_here = |error| LoadError::Included {
+            path: path.to_path_buf(),
+            line: span.line,
+            error: Box::new(error),
+        };
+        for included in resolve(root, path, &target).map_err(included_here)? {
+            if let Ok(canonical) = fs::canonicalize(&included)
+                && stack.contains(&canonical)
+            {
+                return Err(LoadError::IncludeCycle {
+                    path: path.to_path_buf(),
+                    line: span.line,
+                    target: included,
+                });
+            }
+            splice(config, root, &included, options, stack).map_err(included_here)?;
+        }
+    }
+    config.absorb(pending, options, Some(path)).map_err(parse)?;
+    stack.pop();
+    Ok(())
+}
+
+// include に書かれたパスを、読み込むファイルの一覧にする
+fn resolve(root: &Path, including: &Path, target: &str) -> Result<Vec<PathBuf>, LoadError> {
+    let path = match Path::new(target).strip_prefix("/") {
+        Ok(relative) => root.join(relative),
+        Err(_) => including.parent().unwrap_or(Path::new("")).join(target),
+    };
+    let Some(name) = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .filter(|name| pattern::is_glob(name))
+    else {
+        return Ok(vec![path]);
+    };
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let io = |error| LoadError::Io {
+        path: dir.to_path_buf(),
+        error,
+    };
+    // ディレクトリがなければマッチするファイルもない
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(io(error)),
+    };
+    let mut matches = Vec::new();
+    for dir_entry in read_dir {
+        let candidate = dir_entry.map_err(io)?.path();
+        if candidate
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| pattern::file_name_match(name, n))
+        {
+            matches.push(candidate);
+        }
+    }
+    matches.sort();
+    Ok(matches)
+}
+
+// === テスト ===
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IncludeSyntax;
+    use crate::loader::tests::{temp_root, write};
+
+    fn options(includes: IncludeSyntax) -> ParseOptions {
+        ParseOptions {
+            includes,
+            ..ParseOptions::default()
+        }
+    }
+
+    #[test]
+    fn included_entries_are_spliced_in_place() {
+        let root = temp_root();
+        write(
+            &root,
+            "sysctl.conf",
+            "vm.swappiness = 60\n.include conf.d/*.conf\nkernel.pid_max = 1\n",
+        );
+        write(&root, "conf.d/10-vm.conf", "vm.swappiness = 10\n");
+        write(
+            &root,
+            "conf.d/20-kernel.conf",
+            "kernel.pid_max = 2\nnet.ipv4.ip_forward = 1\n",
+        );
+        write(&root, "conf.d/README", "not included");
+
+        let path = root.join("sysctl.conf");
+        let config = Config::from_file(&path, &options(IncludeSyntax::Directive)).unwrap();
+        assert_eq!(config.get("vm.swappiness"), Some("10"));
+        assert_eq!(config.get("kernel.pid_max"), Some("1"));
+        assert_eq!(
+            config.entry("net.ipv4.ip_forward").unwrap().source(),
+            Some(root.join("conf.d/20-kernel.conf").as_path())
+        );
+        // include を有効にしなければ .include は書式の誤り
+        assert!(matches!(
+            Config::from_file(&path, &ParseOptions::default()),
+            Err(LoadError::Parse { .. })
+        ));
+
+        // グロブのディレクトリがなくても、マッチするファイルが1つもないだけ
+        write(
+            &root,
+            "empty.conf",
+            "vm.swappiness = 60\n.include missing.d/*.conf\n",
+        );
+        let config =
+            Config::from_file(root.join("empty.conf"), &options(IncludeSyntax::Directive)).unwrap();
+        assert_eq!(config.get("vm.swappiness"), Some("60"));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn errors_carry_the_include_chain() {
+        let root = temp_root();
+        write(&root, "main.conf", "debug = true\ninclude = sub/a.conf\n");
+        write(&root, "sub/a.conf", "include = b.conf\n");
+        write(&root, "sub/b.conf", "oops\n");
+        write(&root, "loop.conf", "include = sub/loop.conf\n");
+        write(&root, "sub/loop.conf", "include = /loop.conf\n");
+
+        let options = options(IncludeSyntax::Key);
+        let err = load(&root, &root.join("main.conf"), &options).unwrap_err();
+        assert_eq!(
+            err.include_chain(),
+            vec![
+                (root.join("main.conf").as_path(), 2),
+                (root.join("sub/a.conf").as_path(), 1)
+            ]
+        );
+        assert!(matches!(
+            err.innermost(),
+            LoadError::Parse { path, .. } if path.ends_with("sub/b.conf")
+        ));
+
+        let err = load(&root, &root.join("loop.conf"), &options).unwrap_err();
+        assert!(matches!(
+            err.innermost(),
+            LoadError::IncludeCycle { target, .. } if *target == root.join("loop.conf")
+        ));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
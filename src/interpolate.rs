@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{Config, Entry, Span, normalize_key};
+
+// === 値の展開 ===
+//
+// Config::interpolate を呼んだときだけ、値の中の ${name} を展開する。
+// name が同じ Config のキーならその（展開済みの）値、なければ呼び出し側が渡す変数を使う。
+// $$ は $ そのものになる。
+//
+//   log.dir = ${LOG_DIR}
+//   log.file = ${log.dir}/console.log
+//   log.archive = ${log.file}.1
+
+// === エラー型 ===
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InterpolationError {
+    // key の値にある ${name} が、キーにも変数にも見つからない
+    Undefined {
+        key: String,
+        name: String,
+        span: Span,
+    },
+    // chain は参照をたどった順のキー（最初と最後が同じ）。span は循環を閉じる参照
+    Cycle {
+        key: String,
+        chain: Vec<String>,
+        span: Span,
+    },
+    Unterminated {
+        key: String,
+        span: Span,
+    },
+}
+
+impl fmt::Display for InterpolationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterpolationError::Undefined { key, name, span } => write!(
+                f,
+                "line {}: '{}': undefined variable '{}'",
+                span.line, key, name
+            ),
+            InterpolationError::Cycle { key, chain, span } => write!(
+                f,
+                "line {}: '{}': reference cycle: {}",
+                span.line,
+                key,
+                chain.join(" -> ")
+            ),
+            InterpolationError::Unterminated { key, span } => {
+                write!(f, "line {}: '{}': unterminated '${{'", span.line, key)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InterpolationError {}
+
+impl Config {
+    // 値を展開した Config を返す。self と Entry::raw_value は書かれたままの値を保つ
+    pub fn interpolate<F>(&self, vars: F) -> Result<Config, InterpolationError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut resolver = Resolver {
+            config: self,
+            vars: &vars,
+            resolved: HashMap::new(),
+            stack: Vec::new(),
+        };
+        let mut config = self.clone();
+        // エラーが毎回同じものになるよう、キーの順に展開する
+        let mut keys: Vec<&String> = self.entries.keys().collect();
+        keys.sort();
+        for key in keys {
+            let entries = config.entries.get_mut(key).unwrap();
+            let (last, rest) = entries.split_last_mut().unwrap();
+            for entry in rest {
+                entry.value = resolver.expand(key, entry)?;
+            }
+            last.value = resolver.value(key)?;
+        }
+        for (pattern, entry) in &mut config.globs {
+            entry.value = resolver.expand(pattern, entry)?;
+        }
+        // explain が有効な定義を見つけられるよう履歴も展開する。
+        // 上書きされた定義のエラーは無視して書かれたままにする
+        for (key, entries) in &mut config.history {
+            for entry in entries {
+                if let Ok(value) = resolver.expand(key, entry) {
+                    entry.value = value;
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+struct Resolver<'a, F> {
+    config: &'a Config,
+    vars: &'a F,
+    // 展開済みの有効な値
+    resolved: HashMap<String, String>,
+    // 展開中のキー（循環の検出用）
+    stack: Vec<String>,
+}
+
+impl<F> Resolver<'_, F>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    // key の有効なエントリの展開済みの値
+    fn value(&mut self, key: &str) -> Result<String, InterpolationError> {
+        if let Some(value) = self.resolved.get(key) {
+            return Ok(value.clone());
+        }
+        let entry = self.config.entry(key).unwrap();
+        self.stack.push(key.to_string());
+        let value = self.expand(key, entry);
+        self.stack.pop();
+        let value = value?;
+        self.resolved.insert(key.to_string(), value.clone());
+        Ok(value)
+    }
+
+    fn expand(&mut self, key: &str, entry: &Entry) -> Result<String, InterpolationError> {
+        let text = entry.value();
+        let mut expanded = String::new();
+        let mut rest = 0;
+        while let Some(i) = text[rest..].find('$').map(|i| rest + i) {
+            expanded.push_str(&text[rest..i]);
+            let after = &text[i + 1..];
+            if after.starts_with('$') {
+                expanded.push('$');
+                rest = i + 2;
+            } else if let Some(body) = after.strip_prefix('{') {
+                let close = body
+                    .find('}')
+                    .ok_or_else(|| InterpolationError::Unterminated {
+                        key: key.to_string(),
+                        span: reference_span(entry, i..text.len()),
+                    })?;
+                let end = i + 2 + close + 1;
+                let span = reference_span(entry, i..end);
+                expanded.push_str(&self.lookup(key, &body[..close], span)?);
+                rest = end;
+            } else {
+                expanded.push('$');
+                rest = i + 1;
+            }
+        }
+        expanded.push_str(&text[rest..]);
+        Ok(expanded)
+    }
+
+    fn lookup(&mut self, key: &str, name: &str, span: Span) -> Result<String, InterpolationError> {
+        let target = normalize_key(name);
+        if self.config.entry(&target).is_none() {
+            return (self.vars)(name).ok_or_else(|| InterpolationError::Undefined {
+                key: key.to_string(),
+                name: name.to_string(),
+                span,
+            });
+        }
+        if let Some(pos) = self.stack.iter().position(|k| *k == target) {
+            let mut chain = self.stack[pos..].to_vec();
+            chain.push(target.into_owned());
+            return Err(InterpolationError::Cycle {
+                key: key.to_string(),
+                chain,
+                span,
+            });
+        }
+        self.value(&target)
+    }
+}
+
+// 値の中の range を指す span。引用符や継続行で値と原文の位置が対応しないときは値全体を指す
+fn reference_span(entry: &Entry, range: std::ops::Range<usize>) -> Span {
+    let value_span = entry.value_span();
+    if entry.value() != entry.raw_value() || value_span.range().len() != entry.value().len() {
+        return value_span;
+    }
+    Span {
+        line: value_span.line,
+        column: value_span.column + entry.value()[..range.start].chars().count(),
+        start: value_span.start + range.start,
+        end: value_span.start + range.end,
+    }
+}
+
+// === テスト ===
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(name: &str) -> Option<String> {
+        (name == "LOG_DIR").then(|| "/var/log/app".to_string())
+    }
+
+    #[test]
+    fn variables_and_other_keys_are_expanded() {
+        let config = Config::parse(
+            "log.archive = ${log.file}.1\nlog.file = ${LOG_DIR}/console.log\nprice = $$5 and $x\n",
+        )
+        .unwrap();
+        let interpolated = config.interpolate(vars).unwrap();
+        assert_eq!(
+            interpolated.get("log.file"),
+            Some("/var/log/app/console.log")
+        );
+        assert_eq!(
+            interpolated.get("log.archive"),
+            Some("/var/log/app/console.log.1")
+        );
+        assert_eq!(interpolated.get("price"), Some("$5 and $x"));
+        // 元の Config は書かれたまま
+        assert_eq!(config.get("log.file"), Some("${LOG_DIR}/console.log"));
+        assert_eq!(
+            interpolated.entry("log.file").unwrap().raw_value(),
+            "${LOG_DIR}/console.log"
+        );
+        assert!(interpolated.explain("log.archive").is_some());
+    }
+
+    #[test]
+    fn undefined_and_cyclic_references_are_errors_with_spans() {
+        let config = Config::parse("a = x ${HOME_DIR}\n").unwrap();
+        let err = config.interpolate(vars).unwrap_err();
+        assert!(matches!(
+            &err,
+            InterpolationError::Undefined { key, name, span }
+                if key == "a" && name == "HOME_DIR" && (span.line, span.column) == (1, 7)
+        ));
+
+        let content = "a = ${b}\nb = ${c}\nc = ${a}\n";
+        let err = Config::parse(content)
+            .unwrap()
+            .interpolate(vars)
+            .unwrap_err();
+        let InterpolationError::Cycle { key, chain, span } = err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert_eq!(key, "c");
+        assert_eq!(chain, vec!["a", "b", "c", "a"]);
+        assert_eq!(&content[span.range()], "${a}");
+    }
+}
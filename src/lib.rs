@@ -1,108 +1,873 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+mod borrowed;
+#[cfg(feature = "serde")]
+mod de;
+mod derive;
+mod diagnostic;
+mod document;
+mod include;
+mod interpolate;
+mod loader;
+mod pattern;
+mod provenance;
+mod quote;
+#[cfg(feature = "serde")]
+mod ser;
+mod stream;
+mod suggest;
+mod typed;
+
+pub use borrowed::{BorrowedConfig, BorrowedEntry};
+#[cfg(feature = "serde")]
+pub use de::{DeError, Deserializer, from_config};
+pub use derive::SysctlSchema;
+pub use diagnostic::{ColorChoice, Diagnostic, Renderer};
+pub use document::{Document, EditError, Line};
+pub use interpolate::InterpolationError;
+pub use loader::{LoadError, Loader};
+pub use provenance::Explanation;
+#[cfg(feature = "serde")]
+pub use ser::{SerError, Serializer, ignore_error, to_string};
+pub use stream::{StreamError, Tokens};
+pub use typed::{GetError, TypedConfig, TypedEntry, Value, validate_typed};
+#[cfg(feature = "derive")]
+pub use toy_sysctl_conf_derive::SysctlSchema;
+
+// === 位置情報 ===
+
+// line と column は1始まり（column は文字単位）、start..end は入力全体でのバイト位置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    // line_text 内の range を、行頭が入力の offset バイト目にある行として Span にする
+    fn in_line(line_text: &str, line_number: usize, offset: usize, range: Range<usize>) -> Self {
+        Span {
+            line: line_number,
+            column: line_text[..range.start].chars().count() + 1,
+            start: offset + range.start,
+            end: offset + range.end,
+        }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    // 1行に収まる text を指す span の中で、text の range の部分
+    fn within(&self, text: &str, range: Range<usize>) -> Self {
+        Span {
+            line: self.line,
+            column: self.column + text[..range.start].chars().count(),
+            start: self.start + range.start,
+            end: self.start + range.end,
+        }
+    }
+}
 
 // === エラー型 ===
 
 #[derive(Debug)]
 pub enum ParseError {
-    InvalidLine { line_number: usize, content: String },
-    InvalidType { line_number: usize, type_name: String },
+    InvalidLine { line_number: usize, content: String, span: Span },
+    InvalidType { line_number: usize, type_name: String, span: Span },
+    DuplicateKey { key: String, first_line: usize, second_line: usize, span: Span },
+    // スキーマのパターン同士が重なり、どちらを優先するか決められない
+    ConflictingPatterns { line_number: usize, pattern: String, other: String, other_line: usize, span: Span },
+    // span は開き引用符から行末まで
+    UnterminatedQuote { line_number: usize, column: usize, span: Span },
+    InvalidEscape { line_number: usize, column: usize, escape: String, span: Span },
+    // segment はキーの中の誤っているセグメント（空のこともある）、invalid はその中の使えない文字
+    InvalidKey { line_number: usize, segment: String, invalid: Option<char>, span: Span },
+    // スキーマの既定値がその型として正しくない
+    InvalidDefault { line_number: usize, value_type: ValueType, default: String, span: Span },
+    // パターンのキーは具体的なキーを決められないので既定値を持てない
+    PatternDefault { line_number: usize, pattern: String, span: Span },
 }
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ParseError::InvalidLine { line_number, content } => {
+            ParseError::InvalidLine { line_number, content, .. } => {
                 write!(f, "line {}: invalid syntax: {}", line_number, content)
             }
-            ParseError::InvalidType { line_number, type_name } => {
+            ParseError::InvalidType { line_number, type_name, .. } => {
                 write!(f, "line {}: unknown type: {}", line_number, type_name)
             }
+            ParseError::DuplicateKey { key, first_line, second_line, .. } => {
+                write!(f, "line {}: '{}' already defined at line {}", second_line, key, first_line)
+            }
+            ParseError::ConflictingPatterns { line_number, pattern, other, other_line, .. } => {
+                write!(
+                    f,
+                    "line {}: '{}' overlaps '{}' (line {}) with a different type",
+                    line_number, pattern, other, other_line
+                )
+            }
+            ParseError::UnterminatedQuote { line_number, column, .. } => {
+                write!(f, "line {}, column {}: unterminated quote", line_number, column)
+            }
+            ParseError::InvalidEscape { line_number, column, escape, .. } => {
+                write!(f, "line {}, column {}: invalid escape: {}", line_number, column, escape)
+            }
+            ParseError::InvalidKey { line_number, segment, invalid, .. } => match invalid {
+                Some(c) => write!(f, "line {}: invalid key segment '{}': '{}' is not allowed", line_number, segment, c),
+                None => write!(f, "line {}: empty key segment", line_number),
+            },
+            ParseError::InvalidDefault { line_number, value_type, default, .. } => {
+                write!(f, "line {}: default '{}' is not a valid {}", line_number, default, value_type)
+            }
+            ParseError::PatternDefault { line_number, pattern, .. } => {
+                write!(f, "line {}: pattern '{}' cannot have a default", line_number, pattern)
+            }
         }
     }
 }
 
 impl std::error::Error for ParseError {}
 
+// パース自体は成功したが注意が必要なもの
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseWarning {
+    DuplicateKey { key: String, first_line: usize, second_line: usize, span: Span },
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseWarning::DuplicateKey { key, first_line, second_line, .. } => {
+                write!(f, "line {}: '{}' already defined at line {}", second_line, key, first_line)
+            }
+        }
+    }
+}
+
+// === パースオプション ===
+
+// 同じキーが複数回定義されたときの扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    // 後の定義で上書きする（警告を記録）
+    #[default]
+    LastWins,
+    // 最初の定義を残す（警告を記録）
+    FirstWins,
+    // ParseError::DuplicateKey にする
+    Error,
+    // すべての定義を残す。get は最後の値、get_all はすべての値を返す
+    CollectAll,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParseOptions {
+    pub duplicates: DuplicatePolicy,
+    // "..." / '...' で囲んだ値の引用符を外し、エスケープを解釈する
+    pub quoted_values: bool,
+    // 行末の \ で次の行に続ける
+    pub line_continuation: bool,
+    pub includes: IncludeSyntax,
+    pub keys: KeyGrammar,
+}
+
+// キーの書式。. か / で区切ったセグメントの並びで、空のセグメントは許さない。
+// セグメントには英数字と _ と -（br-lan などのインターフェース名）、グロブの文字（* ? [...] {a,b}）、
+// allow で加えた文字が使える
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyGrammar {
+    // false なら検査しない
+    strict: bool,
+    extra: String,
+}
+
+impl Default for KeyGrammar {
+    fn default() -> Self {
+        KeyGrammar { strict: true, extra: String::new() }
+    }
+}
+
+impl KeyGrammar {
+    pub fn strict() -> Self {
+        KeyGrammar::default()
+    }
+
+    // どんなキーでも受け付ける
+    pub fn permissive() -> Self {
+        KeyGrammar { strict: false, extra: String::new() }
+    }
+
+    // セグメントに使える文字を加える（"-:" など）
+    pub fn allow(mut self, chars: &str) -> Self {
+        self.extra.push_str(chars);
+        self
+    }
+
+    // 誤りがあれば、そのセグメントの範囲と使えない文字を返す
+    fn check(&self, key: &str) -> Result<(), (Range<usize>, Option<char>)> {
+        if !self.strict {
+            return Ok(());
+        }
+        let mut start = 0;
+        let mut invalid = None;
+        let mut in_class = false;
+        for (i, c) in key.char_indices().chain([(key.len(), '.')]) {
+            match c {
+                ']' if in_class => in_class = false,
+                _ if in_class => {}
+                '.' | '/' => {
+                    if start == i || invalid.is_some() {
+                        return Err((start..i, invalid));
+                    }
+                    start = i + 1;
+                }
+                // 閉じていない [ は使えない文字
+                '[' if key[i + 1..].contains(']') => in_class = true,
+                c if c.is_ascii_alphanumeric() || "_-*?{},".contains(c) || self.extra.contains(c) => {}
+                c => {
+                    invalid.get_or_insert(c);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// 別のファイルを読み込む行の書き方。読み込むのは Config::from_file と Loader だけで、
+// Config::parse は include の行を読み飛ばす
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IncludeSyntax {
+    // include の行はない（.include はエラー、include = ... は普通のキー）
+    #[default]
+    None,
+    // .include path
+    Directive,
+    // include = path
+    Key,
+}
+
 // === Token ===
 
-#[derive(Debug)]
-enum Token {
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
     Comment(String),
     BlankLine,
+    // key は正規化したもの（net/ipv4/ip_forward → net.ipv4.ip_forward）、raw_key は書かれたまま
+    // value は引用符を外してエスケープを解釈したもの、raw_value は書かれたまま
     KeyValue {
         key: String,
+        raw_key: String,
         value: String,
+        raw_value: String,
         ignore_error: bool,
+        key_span: Span,
+        value_span: Span,
+    },
+    // "-key" だけの行。グロブのキーにマッチしても、このキーには適用しない
+    Exclusion {
+        key: String,
+        key_span: Span,
+    },
+    // path はグロブでもよい。相対パスは include を書いたファイルのディレクトリから
+    Include {
+        path: String,
+        span: Span,
     },
 }
 
-fn tokenize(content: &str) -> Result<Vec<Token>, ParseError> {
-    content
-        .lines()
-        .enumerate()
-        .map(|(i, line)| {
-            let trimmed = line.trim();
-            if trimmed.is_empty() {
-                Ok(Token::BlankLine)
-            } else if trimmed.starts_with('#') || trimmed.starts_with(';') {
-                Ok(Token::Comment(trimmed.to_string()))
-            } else if let Some(rest) = trimmed.strip_prefix('-') {
-                let (key, value) = rest.split_once('=').ok_or(ParseError::InvalidLine {
-                    line_number: i + 1,
-                    content: line.to_string(),
-                })?;
-                Ok(Token::KeyValue {
-                    key: key.trim().to_string(),
-                    value: value.trim().to_string(),
-                    ignore_error: true,
-                })
-            } else {
-                let (key, value) =
-                    trimmed
-                        .split_once('=')
-                        .ok_or(ParseError::InvalidLine {
-                            line_number: i + 1,
-                            content: line.to_string(),
-                        })?;
-                Ok(Token::KeyValue {
-                    key: key.trim().to_string(),
-                    value: value.trim().to_string(),
-                    ignore_error: false,
-                })
+// 1行（継続行があれば続く行も含む）を Token に変換する。offset は行頭の入力全体でのバイト位置
+fn tokenize_line(
+    raw: &str,
+    line_number: usize,
+    offset: usize,
+    options: &ParseOptions,
+) -> Result<Token, ParseError> {
+    tokenize_borrowed(raw, line_number, offset, options).map(BorrowedToken::into_owned)
+}
+
+// Token と同じだが、できるだけ入力を借用する（引用符や継続行があるところだけ新しい文字列になる）
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum BorrowedToken<'a> {
+    Comment(Cow<'a, str>),
+    BlankLine,
+    KeyValue {
+        key: Cow<'a, str>,
+        raw_key: Cow<'a, str>,
+        value: Cow<'a, str>,
+        raw_value: Cow<'a, str>,
+        ignore_error: bool,
+        key_span: Span,
+        value_span: Span,
+    },
+    Exclusion { key: Cow<'a, str>, key_span: Span },
+    Include { path: Cow<'a, str>, span: Span },
+}
+
+impl BorrowedToken<'_> {
+    pub(crate) fn into_owned(self) -> Token {
+        match self {
+            BorrowedToken::Comment(text) => Token::Comment(text.into_owned()),
+            BorrowedToken::BlankLine => Token::BlankLine,
+            BorrowedToken::KeyValue { key, raw_key, value, raw_value, ignore_error, key_span, value_span } => {
+                Token::KeyValue {
+                    key: key.into_owned(),
+                    raw_key: raw_key.into_owned(),
+                    value: value.into_owned(),
+                    raw_value: raw_value.into_owned(),
+                    ignore_error,
+                    key_span,
+                    value_span,
+                }
             }
+            BorrowedToken::Exclusion { key, key_span } => Token::Exclusion { key: key.into_owned(), key_span },
+            BorrowedToken::Include { path, span } => Token::Include { path: path.into_owned(), span },
+        }
+    }
+}
+
+pub(crate) fn tokenize_borrowed<'a>(
+    raw: &'a str,
+    line_number: usize,
+    offset: usize,
+    options: &ParseOptions,
+) -> Result<BorrowedToken<'a>, ParseError> {
+    let logical = LogicalLine::join(raw, line_number, offset, options);
+    let line: &str = &logical.text;
+    let trimmed = line.trim();
+    let span = |range: Range<usize>| logical.span(range);
+    let slice = |range: Range<usize>| logical.slice(range);
+    if trimmed.is_empty() {
+        Ok(BorrowedToken::BlankLine)
+    } else if is_comment(trimmed) {
+        Ok(BorrowedToken::Comment(slice(trimmed_range(line, 0..line.len()))))
+    } else if options.includes == IncludeSyntax::Directive
+        && let Some(rest) = trimmed.strip_prefix(".include")
+        && rest.starts_with(char::is_whitespace)
+    {
+        let path = trimmed_range(line, line.find(".include").unwrap() + ".include".len()..line.len());
+        Ok(BorrowedToken::Include { path: slice(path.clone()), span: span(path) })
+    } else if trimmed.starts_with('-') && !trimmed.contains('=') {
+        let key = trimmed_range(line, line.find('-').unwrap() + 1..line.len());
+        check_key(line, key.clone(), line_number, options, span)?;
+        Ok(BorrowedToken::Exclusion {
+            key: normalized(slice(key.clone())),
+            key_span: span(key),
+        })
+    } else {
+        let layout = kv_layout(line).ok_or_else(|| ParseError::InvalidLine {
+            line_number,
+            content: line.to_string(),
+            span: span(trimmed_range(line, 0..line.len())),
+        })?;
+        check_key(line, layout.key.clone(), line_number, options, span)?;
+        let raw_key = slice(layout.key.clone());
+        let raw_value = slice(layout.value.clone());
+        let value = if options.quoted_values && quote::is_quoted(&raw_value) {
+            let at = |i: usize| layout.value.start + i;
+            Cow::Owned(quote::unquote(&raw_value).map_err(|e| match e {
+                quote::QuoteError::Unterminated => {
+                    let span = span(layout.value.clone());
+                    ParseError::UnterminatedQuote { line_number: span.line, column: span.column, span }
+                }
+                quote::QuoteError::InvalidEscape { at: i, len } => {
+                    let span = span(at(i)..at(i + len));
+                    ParseError::InvalidEscape {
+                        line_number: span.line,
+                        column: span.column,
+                        escape: raw_value[i..i + len].to_string(),
+                        span,
+                    }
+                }
+                quote::QuoteError::Trailing { at: i } => {
+                    let span = span(at(i)..layout.value.end);
+                    ParseError::InvalidLine { line_number: span.line, content: line.to_string(), span }
+                }
+            })?)
+        } else {
+            raw_value.clone()
+        };
+        if options.includes == IncludeSyntax::Key && raw_key == "include" {
+            return Ok(BorrowedToken::Include { path: value, span: span(layout.value) });
+        }
+        Ok(BorrowedToken::KeyValue {
+            key: normalized(raw_key.clone()),
+            raw_key,
+            value,
+            raw_value,
+            ignore_error: layout.dash.is_some(),
+            key_span: span(layout.key),
+            value_span: span(layout.value),
         })
+    }
+}
+
+fn check_key(
+    line: &str,
+    key: Range<usize>,
+    line_number: usize,
+    options: &ParseOptions,
+    span: impl Fn(Range<usize>) -> Span,
+) -> Result<(), ParseError> {
+    options.keys.check(&line[key.clone()]).map_err(|(segment, invalid)| {
+        let segment = key.start + segment.start..key.start + segment.end;
+        ParseError::InvalidKey {
+            line_number,
+            segment: line[segment.clone()].to_string(),
+            invalid,
+            span: span(segment),
+        }
+    })
+}
+
+// normalize_key と同じだが、借用していればそのまま借用を保つ
+fn normalized(key: Cow<'_, str>) -> Cow<'_, str> {
+    match key {
+        Cow::Borrowed(key) => normalize_key(key),
+        Cow::Owned(key) => Cow::Owned(normalize_key(&key).into_owned()),
+    }
+}
+
+fn tokenize(content: &str, options: &ParseOptions) -> Result<Vec<Token>, ParseError> {
+    split_entries(content, options)
+        .into_iter()
+        .map(|entry| tokenize_line(entry.raw, entry.line_number, entry.offset, options))
         .collect()
 }
 
+fn is_comment(trimmed: &str) -> bool {
+    trimmed.starts_with('#') || trimmed.starts_with(';')
+}
+
+// 行末の \ の位置（その後ろの空白は無視する）
+fn continuation(line: &str) -> Option<usize> {
+    line.trim_end().strip_suffix('\\').map(str::len)
+}
+
+// 継続行をまとめた入力の1単位。raw は最後の改行を除いたもの（途中の改行は含む）
+pub(crate) struct RawEntry<'a> {
+    pub(crate) raw: &'a str,
+    pub(crate) newline: &'a str,
+    // 最初の物理行の行番号と、その行頭のバイト位置
+    pub(crate) line_number: usize,
+    pub(crate) offset: usize,
+}
+
+// ParseOptions::line_continuation が有効なら、行末が \ の行と次の行を1つにまとめる。
+// コメントと空行は継続しない
+pub(crate) fn split_entries<'a>(content: &'a str, options: &ParseOptions) -> Vec<RawEntry<'a>> {
+    let mut entries = Vec::new();
+    let (mut start, mut end) = (0, 0);
+    let mut first_line = 1;
+    let mut joinable = false;
+    for (i, chunk) in content.split_inclusive('\n').enumerate() {
+        let line = split_newline(chunk).0;
+        if start == end {
+            first_line = i + 1;
+            joinable = options.line_continuation && !line.trim().is_empty() && !is_comment(line.trim());
+        }
+        end += chunk.len();
+        if !(joinable && continuation(line).is_some()) || end == content.len() {
+            let (raw, newline) = split_newline(&content[start..end]);
+            entries.push(RawEntry { raw, newline, line_number: first_line, offset: start });
+            start = end;
+        }
+    }
+    entries
+}
+
+// 継続行をつないだ論理行。
+// 2行目以降は行頭の空白を除き、各行末の \ を取り除いてつなぐ（\ の前の空白は残る）。
+// つなぐ必要がなければ text は入力をそのまま借用し、pieces は空になる
+struct LogicalLine<'a> {
+    text: Cow<'a, str>,
+    line_number: usize,
+    offset: usize,
+    pieces: Vec<Piece<'a>>,
+}
+
+// 論理行の text[start..] が、物理行 physical[skip..] から始まる
+struct Piece<'a> {
+    start: usize,
+    physical: &'a str,
+    skip: usize,
+    line_number: usize,
+    // 物理行の行頭の、入力中のバイト位置
+    offset: usize,
+}
+
+impl<'a> LogicalLine<'a> {
+    fn join(raw: &'a str, line_number: usize, offset: usize, options: &ParseOptions) -> Self {
+        let joinable = options.line_continuation && !is_comment(raw.trim_start());
+        let mut logical = LogicalLine { text: Cow::Borrowed(raw), line_number, offset, pieces: Vec::new() };
+        let joined = raw.contains('\n') || joinable && continuation(raw).is_some();
+        if !joined {
+            return logical;
+        }
+        let mut text = String::new();
+        let mut offset = offset;
+        for (i, chunk) in raw.split('\n').enumerate() {
+            let physical = chunk.strip_suffix('\r').unwrap_or(chunk);
+            let skip = if i == 0 { 0 } else { physical.len() - physical.trim_start().len() };
+            let end = continuation(physical).filter(|_| joinable).map_or(physical.len(), |end| end.max(skip));
+            logical.pieces.push(Piece { start: text.len(), physical, skip, line_number: line_number + i, offset });
+            text.push_str(&physical[skip..end]);
+            offset += chunk.len() + 1;
+        }
+        logical.text = Cow::Owned(text);
+        logical
+    }
+
+    fn slice(&self, range: Range<usize>) -> Cow<'a, str> {
+        match &self.text {
+            Cow::Borrowed(text) => Cow::Borrowed(&text[range]),
+            Cow::Owned(text) => Cow::Owned(text[range].to_string()),
+        }
+    }
+
+    // text 内の range を、元の入力での位置にする。
+    // 複数の物理行にまたがるときは、行と列は始まりの位置を指す
+    fn span(&self, range: Range<usize>) -> Span {
+        if self.pieces.is_empty() {
+            return Span::in_line(&self.text, self.line_number, self.offset, range);
+        }
+        let first = self.pieces.iter().rev().find(|p| p.start <= range.start).unwrap();
+        let last = if range.is_empty() {
+            first
+        } else {
+            self.pieces.iter().rev().find(|p| p.start < range.end).unwrap()
+        };
+        let position = |piece: &Piece, at: usize| piece.skip + at - piece.start;
+        let start = position(first, range.start);
+        let mut span = Span::in_line(first.physical, first.line_number, first.offset, start..start);
+        span.end = last.offset + position(last, range.end);
+        span
+    }
+}
+
+// sysctl(8) と同じく、最初の区切りが / のキーは / 区切りとみなし、. と / を入れ替える。
+// net/ipv4/conf/eth0.100/rp_filter → net.ipv4.conf.eth0/100.rp_filter
+pub fn normalize_key(key: &str) -> Cow<'_, str> {
+    match key.find(['.', '/']) {
+        Some(i) if key.as_bytes()[i] == b'/' => Cow::Owned(
+            key.chars()
+                .map(|c| match c {
+                    '/' => '.',
+                    '.' => '/',
+                    c => c,
+                })
+                .collect(),
+        ),
+        _ => Cow::Borrowed(key),
+    }
+}
+
+// KeyValue 行のうちキー・値・- が行内のどこにあるか
+pub(crate) struct KvLayout {
+    pub(crate) dash: Option<usize>,
+    pub(crate) key: Range<usize>,
+    pub(crate) eq: usize,
+    pub(crate) value: Range<usize>,
+}
+
+pub(crate) fn kv_layout(line: &str) -> Option<KvLayout> {
+    let mut start = line.len() - line.trim_start().len();
+    let dash = line[start..].starts_with('-').then_some(start);
+    if dash.is_some() {
+        start += 1;
+    }
+    let eq = start + line[start..].find('=')?;
+    Some(KvLayout {
+        dash,
+        key: trimmed_range(line, start..eq),
+        eq,
+        value: trimmed_range(line, eq + 1..line.len()),
+    })
+}
+
+fn trimmed_range(line: &str, range: Range<usize>) -> Range<usize> {
+    let s = &line[range.clone()];
+    let start = range.start + (s.len() - s.trim_start().len());
+    let end = range.end - (s.len() - s.trim_end().len());
+    start..end.max(start)
+}
+
+// str::lines() と同じく "\r\n" と "\n" を改行として扱う
+pub(crate) fn split_newline(chunk: &str) -> (&str, &str) {
+    if let Some(raw) = chunk.strip_suffix("\r\n") {
+        (raw, "\r\n")
+    } else if let Some(raw) = chunk.strip_suffix('\n') {
+        (raw, "\n")
+    } else {
+        (chunk, "")
+    }
+}
+
 // === Config ===
 
-#[derive(Debug)]
+// DuplicatePolicy に従って entries に追加する（Config と BorrowedConfig で共通）
+fn insert_with_policy<K, E>(
+    entries: &mut HashMap<K, Vec<E>>,
+    warnings: &mut Vec<ParseWarning>,
+    key: K,
+    entry: E,
+    key_span: fn(&E) -> Span,
+    policy: DuplicatePolicy,
+) -> Result<(), ParseError>
+where
+    K: AsRef<str> + Eq + std::hash::Hash,
+{
+    let Some(existing) = entries.get_mut(&key) else {
+        entries.insert(key, vec![entry]);
+        return Ok(());
+    };
+    let first_line = key_span(existing.last().unwrap()).line;
+    let span = key_span(&entry);
+    let second_line = span.line;
+    let key = key.as_ref().to_string();
+    match policy {
+        DuplicatePolicy::LastWins => *existing = vec![entry],
+        DuplicatePolicy::FirstWins => {}
+        DuplicatePolicy::CollectAll => {
+            existing.push(entry);
+            return Ok(());
+        }
+        DuplicatePolicy::Error => {
+            return Err(ParseError::DuplicateKey { key, first_line, second_line, span });
+        }
+    }
+    warnings.push(ParseWarning::DuplicateKey { key, first_line, second_line, span });
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    // 書かれたままのキー（/ 区切りのこともある）
+    raw_key: String,
+    value: String,
+    // 書かれたままの値（引用符やエスケープを含む）
+    raw_value: String,
+    // 先頭に - が付いていた（エラー時に無視する）
+    ignore_error: bool,
+    key_span: Span,
+    value_span: Span,
+    // 読み込んだファイル。文字列からパースしたときは None
+    source: Option<PathBuf>,
+}
+
+impl Entry {
+    pub fn raw_key(&self) -> &str {
+        &self.raw_key
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn raw_value(&self) -> &str {
+        &self.raw_value
+    }
+
+    pub fn ignore_error(&self) -> bool {
+        self.ignore_error
+    }
+
+    pub fn line_number(&self) -> usize {
+        self.key_span.line
+    }
+
+    pub fn key_span(&self) -> Span {
+        self.key_span
+    }
+
+    pub fn value_span(&self) -> Span {
+        self.value_span
+    }
+
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct Config {
-    entries: HashMap<String, String>,
+    // CollectAll のときだけ1つのキーに複数のエントリが入る
+    entries: HashMap<String, Vec<Entry>>,
+    // net.ipv4.conf.*.rp_filter のようなグロブのキー（定義順）
+    globs: Vec<(String, Entry)>,
+    // "-key" で除外されたキー
+    excluded: HashSet<String>,
+    // 上書きされたものも含めた、キーごとのすべての定義（適用順）
+    history: HashMap<String, Vec<Entry>>,
+    warnings: Vec<ParseWarning>,
 }
 
 impl Config {
     pub fn parse(content: &str) -> Result<Self, ParseError> {
-        let entries = tokenize(content)?
-            .into_iter()
-            .filter_map(|token| match token {
-                Token::KeyValue { key, value, .. } => Some((key, value)),
-                _ => None,
-            })
-            .collect();
+        Config::parse_with(content, &ParseOptions::default())
+    }
+
+    pub fn parse_with(content: &str, options: &ParseOptions) -> Result<Self, ParseError> {
+        Config::from_tokens(tokenize(content, options)?, options)
+    }
+
+    // 誤りのある行を飛ばして最後まで読み、読めた分の Config とすべてのエラー（行の順）を返す
+    pub fn parse_recovering(content: &str, options: &ParseOptions) -> (Self, Vec<ParseError>) {
+        let mut config = Config::default();
+        let mut errors = Vec::new();
+        for entry in split_entries(content, options) {
+            let result = tokenize_line(entry.raw, entry.line_number, entry.offset, options)
+                .and_then(|token| config.absorb([token], options, None));
+            if let Err(e) = result {
+                errors.push(e);
+            }
+        }
+        (config, errors)
+    }
 
-        Ok(Config { entries })
+    fn from_tokens<I>(tokens: I, options: &ParseOptions) -> Result<Self, ParseError>
+    where
+        I: IntoIterator<Item = Token>,
+    {
+        let mut config = Config::default();
+        config.absorb(tokens, options, None)?;
+        Ok(config)
+    }
+
+    // tokens のエントリを後ろに追加する。source は読み込み元のファイル
+    pub(crate) fn absorb<I>(&mut self, tokens: I, options: &ParseOptions, source: Option<&Path>) -> Result<(), ParseError>
+    where
+        I: IntoIterator<Item = Token>,
+    {
+        for token in tokens {
+            if let Token::KeyValue {
+                key,
+                raw_key,
+                value,
+                raw_value,
+                ignore_error,
+                key_span,
+                value_span,
+            } = token
+            {
+                let entry = Entry {
+                    raw_key,
+                    value,
+                    raw_value,
+                    ignore_error,
+                    key_span,
+                    value_span,
+                    source: source.map(Path::to_path_buf),
+                };
+                if pattern::is_glob(&key) {
+                    self.globs.push((key, entry));
+                    continue;
+                }
+                self.history.entry(key.clone()).or_default().push(entry.clone());
+                self.insert(key, entry, options.duplicates)?;
+            } else if let Token::Exclusion { key, .. } = token {
+                self.excluded.insert(key);
+            }
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, key: String, entry: Entry, policy: DuplicatePolicy) -> Result<(), ParseError> {
+        insert_with_policy(&mut self.entries, &mut self.warnings, key, entry, Entry::key_span, policy)
     }
 
     pub fn get(&self, key: &str) -> Option<&str> {
-        self.entries.get(key).map(|s| s.as_str())
+        self.entry(key).map(|e| e.value())
+    }
+
+    // CollectAll でパースしたときに、同じキーのすべての値を定義順に返す
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.entries
+            .get(normalize_key(key).as_ref())
+            .map(|entries| entries.iter().map(|e| e.value()).collect())
+            .unwrap_or_default()
+    }
+
+    // key は / 区切りでもよい
+    pub fn entry(&self, key: &str) -> Option<&Entry> {
+        self.entries
+            .get(normalize_key(key).as_ref())
+            .and_then(|entries| entries.last())
+    }
+
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    // other の値で上書きする。後から読んだファイルを優先するときに使う
+    pub fn merge(&mut self, other: Config) {
+        self.entries.extend(other.entries);
+        self.globs.extend(other.globs);
+        self.excluded.extend(other.excluded);
+        for (key, entries) in other.history {
+            self.history.entry(key).or_default().extend(entries);
+        }
+        self.warnings.extend(other.warnings);
+    }
+
+    // グロブのキーとそのエントリ（定義順）
+    pub fn globs(&self) -> impl Iterator<Item = (&str, &Entry)> {
+        self.globs.iter().map(|(pattern, entry)| (pattern.as_str(), entry))
+    }
+
+    pub fn is_excluded(&self, key: &str) -> bool {
+        self.excluded.contains(normalize_key(key).as_ref())
+    }
+
+    // key に適用されるグロブのエントリ。複数マッチすれば後に定義されたもの
+    fn glob_for(&self, key: &str) -> Option<&Entry> {
+        if self.is_excluded(key) {
+            return None;
+        }
+        self.globs
+            .iter()
+            .rev()
+            .find(|(pattern, _)| pattern::glob_match(pattern, key))
+            .map(|(_, entry)| entry)
+    }
+
+    // グロブを具体的なキーに展開した Config を返す。明示的に書かれたキーはグロブより優先する
+    pub fn expand<'a, I>(&self, keys: I) -> Config
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut expanded = self.clone();
+        for key in keys {
+            let key = normalize_key(key);
+            let key = key.as_ref();
+            if self.entries.contains_key(key) {
+                continue;
+            }
+            if let Some(entry) = self.glob_for(key) {
+                expanded.history.entry(key.to_string()).or_default().push(entry.clone());
+                expanded.entries.insert(key.to_string(), vec![entry.clone()]);
+            }
+        }
+        expanded
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&String, &Entry)> {
+        self.entries
+            .iter()
+            .filter_map(|(key, entries)| Some((key, entries.last()?)))
     }
 }
 
 // === 検証 ===
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum ValueType {
     Str,
     Bool,
@@ -110,12 +875,9 @@ pub enum ValueType {
 }
 
 impl ValueType {
+    // Config::get_parsed などの型付きの取得と同じ判定にする（typed.rs）
     fn is_valid(&self, value: &str) -> bool {
-        match self {
-            ValueType::Str => true,
-            ValueType::Bool => value == "true" || value == "false",
-            ValueType::Integer => value.parse::<i64>().is_ok(),
-        }
+        self.parse(value).is_ok()
     }
 }
 
@@ -129,101 +891,389 @@ impl fmt::Display for ValueType {
     }
 }
 
+#[derive(Debug)]
+pub struct SchemaEntry {
+    value_type: ValueType,
+    // `type?` と書いたか既定値があれば、設定に無くても MissingKey にしない
+    optional: bool,
+    default: Option<Value>,
+    // 直前に続けて書いたコメント（# を除き、複数行なら改行でつなぐ）
+    description: Option<String>,
+    key_span: Span,
+}
+
+impl SchemaEntry {
+    pub fn value_type(&self) -> &ValueType {
+        &self.value_type
+    }
+
+    pub fn is_optional(&self) -> bool {
+        self.optional
+    }
+
+    pub fn default(&self) -> Option<&Value> {
+        self.default.as_ref()
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn key_span(&self) -> Span {
+        self.key_span
+    }
+}
+
 #[derive(Debug)]
 pub struct Schema {
-    entries: HashMap<String, ValueType>,
+    entries: HashMap<String, SchemaEntry>,
+    // net.ipv4.conf.*.rp_filter のようなパターン（定義順）
+    patterns: Vec<(String, SchemaEntry)>,
 }
 
 impl Schema {
     pub fn parse(content: &str) -> Result<Self, ParseError> {
-        let entries = tokenize(content)?
-            .into_iter()
-            .enumerate()
-            .filter_map(|(i, token)| match token {
-                Token::KeyValue { key, value, .. } => Some((i, key, value)),
-                _ => None,
-            })
-            .map(|(i, key, value)| {
-                let vt = match value.as_str() {
-                    "string" => ValueType::Str,
-                    "bool" => ValueType::Bool,
-                    "integer" => ValueType::Integer,
-                    other => return Err(ParseError::InvalidType {
-                        line_number: i + 1,
-                        type_name: other.to_string(),
+        let mut schema = Schema {
+            entries: HashMap::new(),
+            patterns: Vec::new(),
+        };
+        let mut comments = Vec::new();
+        for token in tokenize(content, &ParseOptions::default())? {
+            schema.add(token, &mut comments)?;
+        }
+        Ok(schema)
+    }
+
+    // Config::parse_recovering と同じく、誤りのある行を飛ばしてすべてのエラーを返す
+    pub fn parse_recovering(content: &str) -> (Self, Vec<ParseError>) {
+        let mut schema = Schema {
+            entries: HashMap::new(),
+            patterns: Vec::new(),
+        };
+        let options = ParseOptions::default();
+        let mut errors = Vec::new();
+        let mut comments = Vec::new();
+        for entry in split_entries(content, &options) {
+            let result = tokenize_line(entry.raw, entry.line_number, entry.offset, &options)
+                .and_then(|token| schema.add(token, &mut comments));
+            if let Err(e) = result {
+                comments.clear();
+                errors.push(e);
+            }
+        }
+        (schema, errors)
+    }
+
+    // comments は直前に続くコメント行。次のエントリの説明になる
+    fn add(&mut self, token: Token, comments: &mut Vec<String>) -> Result<(), ParseError> {
+        if let Token::Comment(text) = &token {
+            let text = &text[1..];
+            comments.push(text.strip_prefix(' ').unwrap_or(text).to_string());
+            return Ok(());
+        }
+        let description = std::mem::take(comments);
+        let Token::KeyValue { key, value, key_span, value_span, .. } = token else {
+            return Ok(());
+        };
+        // `type`、`type?`（省略可能）、`type = default`（省略時は既定値）のいずれか
+        let (type_end, default) = match value.find('=') {
+            Some(i) => (value[..i].trim_end().len(), Some(i + 1)),
+            None => (value.len(), None),
+        };
+        let type_part = &value[..type_end];
+        let (type_name, optional) = match type_part.strip_suffix('?') {
+            Some(name) => (name.trim_end(), true),
+            None => (type_part, false),
+        };
+        let vt = match type_name {
+            "string" => ValueType::Str,
+            "bool" => ValueType::Bool,
+            "integer" => ValueType::Integer,
+            other => return Err(ParseError::InvalidType {
+                line_number: value_span.line,
+                type_name: other.to_string(),
+                span: value_span.within(&value, 0..type_name.len()),
+            }),
+        };
+        let default = match default {
+            Some(start) => {
+                let text = value[start..].trim_start();
+                let range = value.len() - text.len()..value.len();
+                let span = value_span.within(&value, range);
+                if pattern::is_glob(&key) {
+                    return Err(ParseError::PatternDefault {
+                        line_number: span.line,
+                        pattern: key,
+                        span,
+                    });
+                }
+                match vt.parse(text) {
+                    Ok(default) => Some(default),
+                    Err(_) => return Err(ParseError::InvalidDefault {
+                        line_number: span.line,
+                        value_type: vt,
+                        default: text.to_string(),
+                        span,
                     }),
-                };
-                Ok((key, vt))
-            })
-            .collect::<Result<HashMap<_, _>, _>>()?;
-        Ok(Schema { entries })
+                }
+            }
+            None => None,
+        };
+        let entry = SchemaEntry {
+            value_type: vt,
+            optional: optional || default.is_some(),
+            default,
+            description: (!description.is_empty()).then(|| description.join("\n")),
+            key_span,
+        };
+        if pattern::is_glob(&key) {
+            self.check_pattern_conflicts(&key, &entry)?;
+            self.patterns.push((key, entry));
+        } else {
+            self.entries.insert(key, entry);
+        }
+        Ok(())
+    }
+
+    // 同じ具体性で重なるパターンの型が違うと、どちらが適用されるか決められない
+    fn check_pattern_conflicts(&self, key: &str, entry: &SchemaEntry) -> Result<(), ParseError> {
+        for (other, other_entry) in &self.patterns {
+            let conflicting = other_entry.value_type != entry.value_type
+                && pattern::overlap_ties(key, other);
+            if conflicting {
+                return Err(ParseError::ConflictingPatterns {
+                    line_number: entry.key_span.line,
+                    pattern: key.to_string(),
+                    other: other.clone(),
+                    other_line: other_entry.key_span.line,
+                    span: entry.key_span,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn entry(&self, key: &str) -> Option<&SchemaEntry> {
+        self.entries.get(normalize_key(key).as_ref())
+    }
+
+    // 完全一致を優先し、なければマッチするパターンのうち最も具体的なもの（同点なら先に定義したもの）
+    pub fn lookup(&self, key: &str) -> Option<&SchemaEntry> {
+        let key = normalize_key(key);
+        let key = key.as_ref();
+        self.entry(key).or_else(|| {
+            self.patterns
+                .iter()
+                .filter_map(|(pattern, entry)| Some((pattern::match_specificity(pattern, key)?, entry)))
+                .rev()
+                .max_by_key(|(specificity, _)| *specificity)
+                .map(|(_, entry)| entry)
+        })
+    }
+
+    // config のグロブのキーと重なりうるパターン
+    fn overlapping_patterns<'a>(&'a self, glob: &'a str) -> impl Iterator<Item = &'a SchemaEntry> {
+        self.patterns
+            .iter()
+            .filter(move |(pattern, _)| pattern::may_overlap(pattern, glob))
+            .map(|(_, entry)| entry)
     }
 }
 
+// span の指す位置:
+// TypeMismatch は設定ファイルの値、UnknownKey と ProbableTypo は設定ファイルのキー、
+// MissingKey はスキーマファイルのキー
 #[derive(Debug)]
 pub enum ValidationError {
     TypeMismatch {
         key: String,
         expected: String,
         got: String,
+        span: Span,
     },
     UnknownKey {
         key: String,
+        span: Span,
+        // 似ているスキーマのキー（近い順）
+        suggestions: Vec<String>,
     },
     MissingKey {
         key: String,
+        span: Span,
+    },
+    // 似たキー同士の UnknownKey と MissingKey をまとめたもの
+    ProbableTypo {
+        key: String,
+        schema_key: String,
+        span: Span,
+        schema_span: Span,
     },
 }
 
 impl fmt::Display for ValidationError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ValidationError::TypeMismatch { key, expected, got } => {
+            ValidationError::TypeMismatch { key, expected, got, .. } => {
                 write!(f, "'{}': expected {}, got '{}'", key, expected, got)
             }
-            ValidationError::UnknownKey { key } => {
-                write!(f, "'{}': unknown key (not in schema)", key)
+            ValidationError::UnknownKey { key, suggestions, .. } => {
+                write!(f, "'{}': unknown key (not in schema)", key)?;
+                if let Some(suggestion) = suggestions.first() {
+                    write!(f, "; did you mean '{}'?", suggestion)?;
+                }
+                Ok(())
             }
-            ValidationError::MissingKey { key } => {
+            ValidationError::MissingKey { key, .. } => {
                 write!(f, "'{}': missing (required by schema)", key)
             }
+            ValidationError::ProbableTypo { key, schema_key, .. } => {
+                write!(f, "'{}': unknown key, probably a typo of missing '{}'", key, schema_key)
+            }
+        }
+    }
+}
+
+// ignore_error 付きのエントリで起きたエラーは warnings に回す（systemd-sysctl と同じ扱い）
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub errors: Vec<ValidationError>,
+    pub warnings: Vec<ValidationError>,
+}
+
+impl ValidationReport {
+    fn push(&mut self, error: ValidationError, ignore_error: bool) {
+        if ignore_error {
+            self.warnings.push(error);
+        } else {
+            self.errors.push(error);
+        }
+    }
+
+    fn check_type(&mut self, key: &str, vt: &ValueType, entry: &Entry) {
+        if !vt.is_valid(entry.value()) {
+            self.push(
+                ValidationError::TypeMismatch {
+                    key: key.to_string(),
+                    expected: vt.to_string(),
+                    got: entry.value().to_string(),
+                    span: entry.value_span(),
+                },
+                entry.ignore_error(),
+            );
         }
     }
 }
 
 pub fn validate(config: &Config, schema: &Schema) -> Result<(), Vec<ValidationError>> {
-    let mut errors = Vec::new();
+    let report = validate_report(config, schema);
+    if report.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(report.errors)
+    }
+}
+
+pub fn validate_report(config: &Config, schema: &Schema) -> ValidationReport {
+    let mut report = ValidationReport::default();
 
     // schema の各keyについて config の値を型チェック
-    for (key, vt) in &schema.entries {
-        match config.get(key) {
-            Some(value) => {
-                if !vt.is_valid(value) {
-                    errors.push(ValidationError::TypeMismatch {
-                        key: key.clone(),
-                        expected: vt.to_string(),
-                        got: value.to_string(),
-                    });
-                }
+    let mut missing = Vec::new();
+    for (key, schema_entry) in &schema.entries {
+        let vt = &schema_entry.value_type;
+        // グロブの値は下でマッチするすべてのキーについてチェックする
+        match config.entry(key) {
+            Some(entry) => report.check_type(key, vt, entry),
+            None if schema_entry.optional || config.glob_for(key).is_some() => {}
+            None => missing.push((key, schema_entry)),
+        }
+    }
+
+    // スキーマのパターンにマッチするキーはその型でチェックし、
+    // どれにもマッチしなければ UnknownKey
+    let mut unknown: Vec<_> = config
+        .iter()
+        .filter(|(key, entry)| match schema.lookup(key) {
+            Some(schema_entry) if !schema.entries.contains_key(*key) => {
+                report.check_type(key, &schema_entry.value_type, entry);
+                false
             }
-            None => {
-                errors.push(ValidationError::MissingKey { key: key.clone() });
+            Some(_) => false,
+            None => true,
+        })
+        .collect();
+    unknown.sort_by_key(|(_, entry)| entry.key_span().start);
+    for (key, entry) in unknown {
+        let suggestions = suggest::similar_keys(key, schema.entries.keys());
+        // 候補のうち設定に無いキーがあれば、打ち間違いとして1つのエラーにまとめる
+        let typo = suggestions
+            .iter()
+            .find_map(|s| missing.iter().position(|(m, _)| *m == s));
+        match typo {
+            Some(i) => {
+                let (schema_key, schema_entry) = missing.remove(i);
+                report.push(
+                    ValidationError::ProbableTypo {
+                        key: key.clone(),
+                        schema_key: schema_key.clone(),
+                        span: entry.key_span(),
+                        schema_span: schema_entry.key_span,
+                    },
+                    false,
+                );
             }
+            None => report.push(
+                ValidationError::UnknownKey {
+                    key: key.clone(),
+                    span: entry.key_span(),
+                    suggestions,
+                },
+                entry.ignore_error(),
+            ),
         }
     }
 
-    // config に schema にないkeyがあれば UnknownKey
-    for key in config.entries.keys() {
-        if !schema.entries.contains_key(key) {
-            errors.push(ValidationError::UnknownKey { key: key.clone() });
+    // グロブの値は、明示的に上書きされていてもマッチするすべてのキーとパターンの型でチェックする。
+    // どれにもマッチしないグロブは UnknownKey
+    for (pattern, entry) in config.globs() {
+        let mut matched = false;
+        for (key, schema_entry) in &schema.entries {
+            if pattern::glob_match(pattern, key) && !config.is_excluded(key) {
+                report.check_type(key, &schema_entry.value_type, entry);
+                matched = true;
+            }
+        }
+        let mut checked: Vec<&ValueType> = Vec::new();
+        for schema_entry in schema.overlapping_patterns(pattern) {
+            // 同じ型のパターンが複数重なっても、エラーは1つにする
+            if !checked.contains(&&schema_entry.value_type) {
+                report.check_type(pattern, &schema_entry.value_type, entry);
+                checked.push(&schema_entry.value_type);
+            }
+            matched = true;
+        }
+        if !matched {
+            report.push(
+                ValidationError::UnknownKey {
+                    key: pattern.to_string(),
+                    span: entry.key_span(),
+                    suggestions: Vec::new(),
+                },
+                entry.ignore_error(),
+            );
         }
     }
 
-    if errors.is_empty() {
-        Ok(())
-    } else {
-        Err(errors)
+    for (key, schema_entry) in missing {
+        report.push(
+            ValidationError::MissingKey {
+                key: key.clone(),
+                span: schema_entry.key_span,
+            },
+            false,
+        );
     }
+
+    report
 }
 
 // === テスト ===
@@ -259,7 +1309,7 @@ mod tests {
     }
 
     // KV行・コメント行・空行をランダムに混ぜた設定ファイル全体
-    fn arb_config_content() -> impl Strategy<Value = String> {
+    pub(crate) fn arb_config_content() -> impl Strategy<Value = String> {
         prop::collection::vec(
             prop_oneof![
                 arb_key_value_line(),
@@ -324,7 +1374,7 @@ retry = 1
 extra = x").unwrap();
         let schema = Schema::parse("retry = integer").unwrap();
         let errors = validate(&config, &schema).unwrap_err();
-        assert!(matches!(&errors[0], ValidationError::UnknownKey { key } if key == "extra"));
+        assert!(matches!(&errors[0], ValidationError::UnknownKey { key, .. } if key == "extra"));
     }
 
     // --- スキーマパース ---
@@ -347,6 +1397,50 @@ debug = bool").unwrap();
         assert!(matches!(err, ParseError::InvalidType { line_number: 1, .. }));
     }
 
+    #[test]
+    fn schema_marks_optional_keys_and_checks_defaults() {
+        let content = "debug = bool?\nretry = integer = 3\nmotd = string = a = b\nlog.file = string\n";
+        let schema = Schema::parse(content).unwrap();
+        assert!(schema.entry("debug").unwrap().is_optional());
+        assert_eq!(schema.entry("retry").unwrap().default(), Some(&Value::Integer(3)));
+        assert!(schema.entry("retry").unwrap().is_optional());
+        assert_eq!(schema.entry("motd").unwrap().default(), Some(&Value::Str("a = b".to_string())));
+        assert!(!schema.entry("log.file").unwrap().is_optional());
+        // 省略可能なキーは無くても MissingKey にならない
+        let errors = validate(&Config::parse("").unwrap(), &schema).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], ValidationError::MissingKey { key, .. } if key == "log.file"));
+
+        let content = "retry = integer = three\n";
+        let err = Schema::parse(content).unwrap_err();
+        assert!(matches!(
+            err,
+            ParseError::InvalidDefault { value_type: ValueType::Integer, ref default, span, .. }
+                if default == "three" && &content[span.range()] == "three"
+        ));
+        let err = Schema::parse("net.ipv4.conf.*.rp_filter = integer = 1").unwrap_err();
+        assert!(matches!(err, ParseError::PatternDefault { line_number: 1, .. }));
+        let err = Schema::parse("debug = boolean?").unwrap_err();
+        assert!(matches!(err, ParseError::InvalidType { ref type_name, .. } if type_name == "boolean"));
+    }
+
+    #[test]
+    fn comments_directly_above_an_entry_describe_it() {
+        let schema = Schema::parse("\
+# ネットワーク設定
+
+# 接続先
+#
+# host:port
+endpoint = string
+; 再試行の回数
+retry = integer
+debug = bool").unwrap();
+        assert_eq!(schema.entry("endpoint").unwrap().description(), Some("接続先\n\nhost:port"));
+        assert_eq!(schema.entry("retry").unwrap().description(), Some("再試行の回数"));
+        assert_eq!(schema.entry("debug").unwrap().description(), None);
+    }
+
     // --- 入力例による結合テスト ---
 
     #[test]
@@ -363,7 +1457,7 @@ log.file = string
 log.name = string").unwrap();
         let errors = validate(&config, &schema).unwrap_err();
         assert_eq!(errors.len(), 1);
-        assert!(matches!(&errors[0], ValidationError::MissingKey { key } if key == "log.name"));
+        assert!(matches!(&errors[0], ValidationError::MissingKey { key, .. } if key == "log.name"));
     }
 
     #[test]
@@ -381,6 +1475,422 @@ log.file = string
 log.name = string").unwrap();
         let errors = validate(&config, &schema).unwrap_err();
         assert_eq!(errors.len(), 1);
-        assert!(matches!(&errors[0], ValidationError::MissingKey { key } if key == "debug"));
+        assert!(matches!(&errors[0], ValidationError::MissingKey { key, .. } if key == "debug"));
+    }
+
+    // --- ignore_error: 先頭 - 付きのエントリのエラーは警告になる ---
+
+    #[test]
+    fn ignore_error_flag_is_kept_on_entries() {
+        let config = Config::parse("\
+-net.ipv4.conf.default.rp_filter = 1
+debug = true").unwrap();
+        assert!(config.entry("net.ipv4.conf.default.rp_filter").unwrap().ignore_error());
+        assert!(!config.entry("debug").unwrap().ignore_error());
+    }
+
+    #[test]
+    fn errors_on_ignore_error_entries_become_warnings() {
+        let config = Config::parse("\
+-retry = abc
+-net.ipv4.conf.default.rp_filter = 1
+debug = true").unwrap();
+        let schema = Schema::parse("\
+retry = integer
+debug = bool").unwrap();
+        assert!(validate(&config, &schema).is_ok());
+
+        let report = validate_report(&config, &schema);
+        assert!(report.errors.is_empty());
+        assert_eq!(report.warnings.len(), 2);
+        assert!(report.warnings.iter().any(|w| matches!(w, ValidationError::TypeMismatch { key, .. } if key == "retry")));
+        assert!(report.warnings.iter().any(|w| matches!(w, ValidationError::UnknownKey { key, .. } if key == "net.ipv4.conf.default.rp_filter")));
+    }
+
+    // --- 重複キー: ポリシーごとの扱い ---
+
+    #[test]
+    fn duplicate_key_is_last_wins_with_warning_by_default() {
+        // proptest-regressions に残っている "j = \nj = " と同じ形
+        let config = Config::parse("j = 1\nj = 2").unwrap();
+        assert_eq!(config.get("j"), Some("2"));
+        assert_eq!(config.warnings(), &[ParseWarning::DuplicateKey {
+            key: "j".to_string(),
+            first_line: 1,
+            second_line: 2,
+            span: Span { line: 2, column: 1, start: 6, end: 7 },
+        }]);
+    }
+
+    #[test]
+    fn duplicate_key_policies() {
+        let content = "\
+vm.swappiness = 60
+debug = true
+vm.swappiness = 10";
+        let parse = |duplicates| Config::parse_with(content, &ParseOptions { duplicates, ..ParseOptions::default() });
+
+        assert_eq!(parse(DuplicatePolicy::FirstWins).unwrap().get("vm.swappiness"), Some("60"));
+
+        let all = parse(DuplicatePolicy::CollectAll).unwrap();
+        assert_eq!(all.get_all("vm.swappiness"), vec!["60", "10"]);
+        assert!(all.warnings().is_empty());
+
+        let err = parse(DuplicatePolicy::Error).unwrap_err();
+        assert!(matches!(err, ParseError::DuplicateKey { first_line: 1, second_line: 3, .. }));
+    }
+
+    // --- 位置情報: エントリとエラーが行・列・バイト位置を持つ ---
+
+    #[test]
+    fn entries_record_key_and_value_spans() {
+        let content = "# comment\n  -log.file =  /var/log/console.log\n";
+        let config = Config::parse(content).unwrap();
+        let entry = config.entry("log.file").unwrap();
+        assert_eq!(entry.key_span(), Span { line: 2, column: 4, start: 13, end: 21 });
+        assert_eq!(entry.value_span().column, 16);
+        assert_eq!(&content[entry.value_span().range()], "/var/log/console.log");
+    }
+
+    #[test]
+    fn validation_errors_point_at_config_and_schema_lines() {
+        let config = Config::parse("\
+retry = abc
+extra = x").unwrap();
+        let schema = Schema::parse("\
+# 再試行回数
+retry = integer
+
+log.name = string").unwrap();
+        let errors = validate(&config, &schema).unwrap_err();
+        for error in &errors {
+            match error {
+                ValidationError::TypeMismatch { span, .. } => assert_eq!((span.line, span.column), (1, 9)),
+                ValidationError::UnknownKey { span, .. } => assert_eq!((span.line, span.column), (2, 1)),
+                ValidationError::MissingKey { span, .. } => assert_eq!((span.line, span.column), (4, 1)),
+                ValidationError::ProbableTypo { .. } => unreachable!(),
+            }
+        }
+        assert_eq!(errors.len(), 3);
+    }
+
+    #[test]
+    fn schema_type_error_reports_its_own_line_and_column() {
+        let err = Schema::parse("# comment\n\nretry =  number").unwrap_err();
+        assert!(matches!(err, ParseError::InvalidType { line_number: 3, span: Span { column: 10, .. }, .. }));
+    }
+
+    // --- 打ち間違い: 似たキーを提案し、対になる MissingKey とまとめる ---
+
+    #[test]
+    fn unknown_key_suggests_similar_schema_keys() {
+        let config = Config::parse("\
+log.file = /var/log/console.log
+log.flie = /tmp/x").unwrap();
+        let schema = Schema::parse("log.file = string").unwrap();
+        let errors = validate(&config, &schema).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], ValidationError::UnknownKey { suggestions, .. } if suggestions == &["log.file"]));
+        assert_eq!(errors[0].to_string(), "'log.flie': unknown key (not in schema); did you mean 'log.file'?");
+    }
+
+    #[test]
+    fn unknown_and_missing_pair_is_reported_as_probable_typo() {
+        let config = Config::parse("\
+endpoint = localhost:3000
+log.flie = /var/log/console.log").unwrap();
+        let schema = Schema::parse("\
+endpoint = string
+log.file = string").unwrap();
+        let errors = validate(&config, &schema).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            &errors[0],
+            ValidationError::ProbableTypo { key, schema_key, span, schema_span }
+                if key == "log.flie" && schema_key == "log.file" && span.line == 2 && schema_span.line == 2
+        ));
+    }
+
+    // --- グロブ: net.ipv4.conf.*.rp_filter のようなキー ---
+
+    #[test]
+    fn glob_keys_are_kept_apart_and_expanded() {
+        let config = Config::parse("\
+net.ipv4.conf.*.rp_filter = 2
+-net.ipv4.conf.lo.rp_filter
+net.ipv4.conf.eth1.rp_filter = 0").unwrap();
+        assert_eq!(config.get("net.ipv4.conf.*.rp_filter"), None);
+        assert_eq!(config.globs().count(), 1);
+        assert!(config.is_excluded("net.ipv4.conf.lo.rp_filter"));
+
+        let expanded = config.expand([
+            "net.ipv4.conf.eth0.rp_filter",
+            "net.ipv4.conf.eth1.rp_filter",
+            "net.ipv4.conf.lo.rp_filter",
+            "net.ipv4.ip_forward",
+        ]);
+        assert_eq!(expanded.get("net.ipv4.conf.eth0.rp_filter"), Some("2"));
+        assert_eq!(expanded.get("net.ipv4.conf.eth1.rp_filter"), Some("0"));
+        assert_eq!(expanded.get("net.ipv4.conf.lo.rp_filter"), None);
+        assert_eq!(expanded.get("net.ipv4.ip_forward"), None);
+    }
+
+    #[test]
+    fn glob_value_is_checked_against_every_matching_schema_key() {
+        let schema = Schema::parse("\
+net.ipv4.conf.eth0.rp_filter = integer
+net.ipv4.conf.eth1.rp_filter = integer
+net.ipv4.conf.lo.rp_filter = bool").unwrap();
+
+        let config = Config::parse("\
+net.ipv4.conf.*.rp_filter = 2
+-net.ipv4.conf.lo.rp_filter
+net.ipv4.conf.lo.rp_filter = true").unwrap();
+        assert!(validate(&config, &schema).is_ok());
+
+        let config = Config::parse("\
+net.ipv4.conf.*.rp_filter = strict
+net.ipv6.conf.*.forwarding = 1").unwrap();
+        let errors = validate(&config, &schema).unwrap_err();
+        let mismatches = errors.iter().filter(|e| matches!(e, ValidationError::TypeMismatch { span, .. } if span.line == 1)).count();
+        assert_eq!(mismatches, 3);
+        assert!(errors.iter().any(|e| matches!(e, ValidationError::UnknownKey { key, .. } if key == "net.ipv6.conf.*.forwarding")));
+    }
+
+    #[test]
+    fn glob_value_is_checked_even_when_every_key_is_overridden() {
+        let schema = Schema::parse("\
+net.ipv4.conf.all.rp_filter = integer
+net.ipv4.conf.lo.rp_filter = integer
+net.ipv6.conf.*.forwarding = bool").unwrap();
+        let config = Config::parse("\
+net.ipv4.conf.*.rp_filter = abc
+net.ipv4.conf.all.rp_filter = 1
+net.ipv4.conf.lo.rp_filter = 0
+net.ipv6.conf.*.forwarding = 2
+net.ipv6.conf.eth0.forwarding = true").unwrap();
+        let errors = validate(&config, &schema).unwrap_err();
+        let lines: Vec<usize> = errors
+            .iter()
+            .map(|e| match e {
+                ValidationError::TypeMismatch { span, .. } => span.line,
+                other => panic!("unexpected error: {:?}", other),
+            })
+            .collect();
+        assert_eq!(lines, vec![1, 1, 4]);
+    }
+
+    // --- スキーマのパターン: 1行で全インターフェースを定義する ---
+
+    #[test]
+    fn schema_patterns_cover_every_interface() {
+        let schema = Schema::parse("\
+net.ipv4.conf.*.rp_filter = integer
+net.ipv6.conf.{all,default,eth*}.disable_ipv6 = bool").unwrap();
+        let config = Config::parse("\
+net.ipv4.conf.eth0.rp_filter = 1
+net.ipv4.conf.wlan0.rp_filter = loose
+net.ipv6.conf.default.disable_ipv6 = true
+net.ipv6.conf.lo.disable_ipv6 = true").unwrap();
+        let errors = validate(&config, &schema).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|e| matches!(e, ValidationError::TypeMismatch { key, .. } if key == "net.ipv4.conf.wlan0.rp_filter")));
+        assert!(errors.iter().any(|e| matches!(e, ValidationError::UnknownKey { key, .. } if key == "net.ipv6.conf.lo.disable_ipv6")));
+    }
+
+    #[test]
+    fn most_specific_schema_pattern_wins() {
+        let schema = Schema::parse("\
+net.ipv4.conf.*.rp_filter = integer
+net.ipv4.conf.eth*.rp_filter = bool
+net.ipv4.conf.eth0.rp_filter = string").unwrap();
+        let vt = |key| schema.lookup(key).map(|e| e.value_type().to_string());
+        assert_eq!(vt("net.ipv4.conf.lo.rp_filter").as_deref(), Some("integer"));
+        assert_eq!(vt("net.ipv4.conf.eth1.rp_filter").as_deref(), Some("bool"));
+        assert_eq!(vt("net.ipv4.conf.eth0.rp_filter").as_deref(), Some("string"));
+    }
+
+    #[test]
+    fn equally_specific_overlapping_patterns_with_different_types_are_rejected() {
+        let err = Schema::parse("\
+net.ipv4.conf.eth*.rp_filter = integer
+net.ipv4.conf.*th0.rp_filter = bool").unwrap_err();
+        assert!(matches!(err, ParseError::ConflictingPatterns { line_number: 2, other_line: 1, .. }));
+        // 型が同じならどちらが選ばれても結果は変わらない
+        assert!(Schema::parse("\
+net.ipv4.conf.eth*.rp_filter = integer
+net.ipv4.conf.*th0.rp_filter = integer").is_ok());
+        // 最初に重なる選択肢の組だけでなく、すべての組を比べる
+        for content in ["a.{x*,*}.c = integer\na.*.c = bool", "a.*.c = bool\na.{x*,*}.c = integer"] {
+            let err = Schema::parse(content).unwrap_err();
+            assert!(matches!(err, ParseError::ConflictingPatterns { line_number: 2, other_line: 1, .. }), "{}", content);
+        }
+    }
+
+    // --- / 区切りのキー ---
+
+    #[test]
+    fn slash_separated_keys_are_normalised() {
+        assert_eq!(normalize_key("net/ipv4/ip_forward"), "net.ipv4.ip_forward");
+        assert_eq!(normalize_key("net/ipv4/conf/eth0.100/rp_filter"), "net.ipv4.conf.eth0/100.rp_filter");
+        assert_eq!(normalize_key("net.ipv4.conf.eth0/100.rp_filter"), "net.ipv4.conf.eth0/100.rp_filter");
+
+        let config = Config::parse("\
+net/ipv4/ip_forward = 1
+net/ipv4/conf/eth0.100/rp_filter = 2").unwrap();
+        assert_eq!(config.get("net.ipv4.ip_forward"), Some("1"));
+        assert_eq!(config.get("net/ipv4/ip_forward"), Some("1"));
+        assert_eq!(config.get("net.ipv4.conf.eth0/100.rp_filter"), Some("2"));
+        assert_eq!(config.entry("net.ipv4.ip_forward").unwrap().raw_key(), "net/ipv4/ip_forward");
+
+        let schema = Schema::parse("\
+net.ipv4.ip_forward = bool
+net.ipv4.conf.*.rp_filter = integer").unwrap();
+        let errors = validate(&config, &schema).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], ValidationError::TypeMismatch { key, .. } if key == "net.ipv4.ip_forward"));
+    }
+
+    #[test]
+    fn both_spellings_of_a_key_are_duplicates() {
+        let config = Config::parse("net.ipv4.ip_forward = 0\nnet/ipv4/ip_forward = 1").unwrap();
+        assert_eq!(config.get("net.ipv4.ip_forward"), Some("1"));
+        assert_eq!(config.warnings().len(), 1);
+    }
+
+    // --- 引用符付きの値 ---
+
+    #[test]
+    fn quoted_values_are_opt_in() {
+        let content = r#"motd = "  hello # world\n"
+path = '/var/log/app.log'"#;
+        let plain = Config::parse(content).unwrap();
+        assert_eq!(plain.get("motd"), Some(r#""  hello # world\n""#));
+
+        let options = ParseOptions { quoted_values: true, ..ParseOptions::default() };
+        let config = Config::parse_with(content, &options).unwrap();
+        assert_eq!(config.get("motd"), Some("  hello # world\n"));
+        assert_eq!(config.get("path"), Some("/var/log/app.log"));
+        assert_eq!(config.entry("motd").unwrap().raw_value(), r#""  hello # world\n""#);
+    }
+
+    #[test]
+    fn quote_errors_report_their_column() {
+        let options = ParseOptions { quoted_values: true, ..ParseOptions::default() };
+        let err = Config::parse_with("debug = true\nmotd = \"hello", &options).unwrap_err();
+        assert!(matches!(err, ParseError::UnterminatedQuote { line_number: 2, column: 8, .. }));
+
+        let err = Config::parse_with(r#"motd = "a\qb""#, &options).unwrap_err();
+        assert!(matches!(&err, ParseError::InvalidEscape { column: 10, escape, .. } if escape == r"\q"));
+    }
+
+    // --- 継続行 ---
+
+    #[test]
+    fn backslash_joins_the_next_line() {
+        let content = "kernel.core_pattern = |/usr/lib/systemd/systemd-coredump \\\n    %P %u %g \\\n    %s %t\n# comment \\\ndebug = true\n";
+        let options = ParseOptions { line_continuation: true, ..ParseOptions::default() };
+        let config = Config::parse_with(content, &options).unwrap();
+        assert_eq!(
+            config.get("kernel.core_pattern"),
+            Some("|/usr/lib/systemd/systemd-coredump %P %u %g %s %t")
+        );
+        assert_eq!(config.get("debug"), Some("true"));
+        assert_eq!(config.entry("debug").unwrap().line_number(), 5);
+        assert!(Config::parse(content).is_err());
+    }
+
+    #[test]
+    fn errors_in_continued_lines_point_at_the_physical_line() {
+        let options = ParseOptions {
+            quoted_values: true,
+            line_continuation: true,
+            ..ParseOptions::default()
+        };
+        let content = "a = 1\nmotd = \"first \\\n  second \\q\"\n";
+        let err = Config::parse_with(content, &options).unwrap_err();
+        let ParseError::InvalidEscape { line_number, column, span, .. } = err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert_eq!((line_number, column), (3, 10));
+        assert_eq!(&content[span.range()], "\\q");
+    }
+
+    // --- エラーからの回復 ---
+
+    #[test]
+    fn recovering_parse_reports_every_bad_line() {
+        let content = "a = 1\noops\nb = 2\nstill broken\na = 3\n";
+        let options = ParseOptions { duplicates: DuplicatePolicy::Error, ..ParseOptions::default() };
+        let (config, errors) = Config::parse_recovering(content, &options);
+        assert_eq!(config.get("a"), Some("1"));
+        assert_eq!(config.get("b"), Some("2"));
+        let lines: Vec<usize> = errors
+            .iter()
+            .map(|e| match e {
+                ParseError::InvalidLine { line_number, .. } => *line_number,
+                ParseError::DuplicateKey { second_line, .. } => *second_line,
+                other => panic!("unexpected error: {:?}", other),
+            })
+            .collect();
+        assert_eq!(lines, vec![2, 4, 5]);
+    }
+
+    #[test]
+    fn recovering_schema_parse_reports_every_unknown_type() {
+        let (schema, errors) = Schema::parse_recovering("a = integer\nb = float\nc = bool\nd = text\n");
+        assert!(schema.entry("c").is_some());
+        assert!(schema.entry("b").is_none());
+        let names: Vec<&str> = errors
+            .iter()
+            .map(|e| match e {
+                ParseError::InvalidType { type_name, .. } => type_name.as_str(),
+                other => panic!("unexpected error: {:?}", other),
+            })
+            .collect();
+        assert_eq!(names, vec!["float", "text"]);
+    }
+
+    // --- キーの書式 ---
+
+    #[test]
+    fn malformed_keys_name_the_bad_segment() {
+        let cases = [
+            ("= 1", "", None),
+            ("a b = c", "a b", Some(' ')),
+            ("foo..bar = 1", "", None),
+            ("net.ipv4:x.y = 1", "ipv4:x", Some(':')),
+            ("-kernel..x", "", None),
+            ("a b[ = 1", "a b[", Some(' ')),
+            ("$[ = 1", "$[", Some('$')),
+            ("net.ipv4[.x = 1", "ipv4[", Some('[')),
+        ];
+        for (content, bad, ch) in cases {
+            match Config::parse(content) {
+                Err(ParseError::InvalidKey { segment, invalid, span, .. }) => {
+                    assert_eq!((segment.as_str(), invalid), (bad, ch), "{}", content);
+                    assert_eq!(&content[span.range()], bad);
+                }
+                other => panic!("{}: unexpected result: {:?}", content, other),
+            }
+        }
+        assert!(Config::parse("net.ipv4.conf.{all,eth[0-9]}.rp_filter = 1").is_ok());
+    }
+
+    #[test]
+    fn dashed_interface_keys_parse_by_default() {
+        let config = Config::parse("net.ipv4.conf.br-lan.rp_filter = 1\nnet.ipv4.conf.veth-1a2b.forwarding = 0").unwrap();
+        assert_eq!(config.get("net.ipv4.conf.br-lan.rp_filter"), Some("1"));
+        assert_eq!(config.get("net/ipv4/conf/veth-1a2b/forwarding"), Some("0"));
+    }
+
+    #[test]
+    fn key_grammar_is_configurable() {
+        let content = "net.ipv4.conf.br-lan.rp_filter = 1\nfoo:bar = 2";
+        assert!(Config::parse(content).is_err());
+        let options = ParseOptions { keys: KeyGrammar::strict().allow(":"), ..ParseOptions::default() };
+        assert_eq!(Config::parse_with(content, &options).unwrap().get("foo:bar"), Some("2"));
+        let options = ParseOptions { keys: KeyGrammar::permissive(), ..ParseOptions::default() };
+        assert!(Config::parse_with("a b = c\n= d", &options).is_ok());
     }
 }
@@ -0,0 +1,326 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{Config, ParseError, ParseOptions, include};
+
+// === エラー型 ===
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        error: ParseError,
+    },
+    // path の line 行目の include が、読み込み中のファイル target を指している
+    IncludeCycle {
+        path: PathBuf,
+        line: usize,
+        target: PathBuf,
+    },
+    // path の line 行目で include したファイルのエラー
+    Included {
+        path: PathBuf,
+        line: usize,
+        error: Box<LoadError>,
+    },
+}
+
+impl LoadError {
+    // エラーになったファイルまでの include の連なり（外側から順に、include を書いたファイルと行）
+    pub fn include_chain(&self) -> Vec<(&Path, usize)> {
+        let mut chain = Vec::new();
+        let mut error = self;
+        while let LoadError::Included {
+            path,
+            line,
+            error: inner,
+        } = error
+        {
+            chain.push((path.as_path(), *line));
+            error = inner;
+        }
+        chain
+    }
+
+    // include を辿った先の、もとのエラー
+    pub fn innermost(&self) -> &LoadError {
+        match self {
+            LoadError::Included { error, .. } => error.innermost(),
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            LoadError::Parse { path, error } => write!(f, "{}: {}", path.display(), error),
+            LoadError::IncludeCycle { path, line, target } => write!(
+                f,
+                "{}:{}: include cycle: {} is already being read",
+                path.display(),
+                line,
+                target.display()
+            ),
+            LoadError::Included { .. } => {
+                write!(f, "{}", self.innermost())?;
+                for (path, line) in self.include_chain().iter().rev() {
+                    write!(f, "\n  included from {}:{}", path.display(), line)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io { error, .. } => Some(error),
+            LoadError::Parse { error, .. } => Some(error),
+            LoadError::IncludeCycle { .. } => None,
+            LoadError::Included { error, .. } => Some(error.as_ref()),
+        }
+    }
+}
+
+// === Loader ===
+//
+// systemd-sysctl と同じ規則で複数のディレクトリから *.conf を読む。
+// - 同じファイル名なら先に並んでいるディレクトリのものが使われ、後ろのものは隠れる
+// - 使われるファイルはディレクトリに関係なくファイル名の辞書順に適用する（後のものが優先）
+// - /dev/null へのシンボリックリンクはそのファイル名を無効にする
+// - 単独ファイル（/etc/sysctl.conf）は最後に適用する
+// - ParseOptions::includes を有効にすると、各ファイルの include も辿る
+
+const DEFAULT_DIRS: [&str; 4] = [
+    "/etc/sysctl.d",
+    "/run/sysctl.d",
+    "/usr/local/lib/sysctl.d",
+    "/usr/lib/sysctl.d",
+];
+
+const DEFAULT_FILES: [&str; 1] = ["/etc/sysctl.conf"];
+
+#[derive(Debug, Clone)]
+pub struct Loader {
+    // dirs と files はこの下にあるものとして扱う（テストで一時ディレクトリを使うため）
+    root: PathBuf,
+    dirs: Vec<PathBuf>,
+    files: Vec<PathBuf>,
+    options: ParseOptions,
+}
+
+impl Default for Loader {
+    fn default() -> Self {
+        Loader {
+            root: PathBuf::from("/"),
+            dirs: DEFAULT_DIRS.iter().map(PathBuf::from).collect(),
+            files: DEFAULT_FILES.iter().map(PathBuf::from).collect(),
+            options: ParseOptions::default(),
+        }
+    }
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        Loader::default()
+    }
+
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    // 優先度の高い順に並べる
+    pub fn dirs<I, P>(mut self, dirs: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.dirs = dirs.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn files<I, P>(mut self, files: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.files = files.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    // 適用する順に並んだファイルの一覧
+    pub fn fragments(&self) -> Result<Vec<PathBuf>, LoadError> {
+        // ファイル名 → 最初に見つかったパス（None は /dev/null で無効化されたもの）
+        let mut by_name: BTreeMap<String, Option<PathBuf>> = BTreeMap::new();
+        for dir in &self.dirs {
+            let dir = self.resolve(dir);
+            let read_dir = match fs::read_dir(&dir) {
+                Ok(read_dir) => read_dir,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(LoadError::Io { path: dir, error }),
+            };
+            for dir_entry in read_dir {
+                let path = dir_entry
+                    .map_err(|error| LoadError::Io {
+                        path: dir.clone(),
+                        error,
+                    })?
+                    .path();
+                let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                    continue;
+                };
+                if !name.ends_with(".conf") || by_name.contains_key(name) {
+                    continue;
+                }
+                // systemd-sysctl と同じく、通常のファイル（とそれを指すリンク）だけを読む。
+                // foo.conf という名前のディレクトリは無視する
+                let masked = is_masked(&path);
+                if !masked && !path.is_file() {
+                    continue;
+                }
+                let name = name.to_string();
+                by_name.insert(name, (!masked).then_some(path));
+            }
+        }
+
+        let mut fragments: Vec<PathBuf> = by_name.into_values().flatten().collect();
+        fragments.extend(
+            self.files
+                .iter()
+                .map(|file| self.resolve(file))
+                .filter(|path| path.exists() && !is_masked(path)),
+        );
+        Ok(fragments)
+    }
+
+    pub fn load(&self) -> Result<Config, LoadError> {
+        let mut config = Config::default();
+        for path in self.fragments()? {
+            config.merge(include::load(&self.root, &path, &self.options)?);
+        }
+        Ok(config)
+    }
+
+    fn resolve(&self, path: &Path) -> PathBuf {
+        self.root.join(path.strip_prefix("/").unwrap_or(path))
+    }
+}
+
+fn is_masked(path: &Path) -> bool {
+    fs::read_link(path).is_ok_and(|target| target == Path::new("/dev/null"))
+}
+
+// === テスト ===
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // テストごとに別の一時ディレクトリを作る。include のテストでも使う
+    pub(crate) fn temp_root() -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let root = std::env::temp_dir().join(format!(
+            "toy-sysctl-conf-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    pub(crate) fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn earlier_directory_masks_same_file_name_and_files_apply_in_name_order() {
+        let root = temp_root();
+        write(
+            &root,
+            "usr/lib/sysctl.d/50-default.conf",
+            "vm.swappiness = 60\nkernel.pid_max = 1\n",
+        );
+        write(
+            &root,
+            "etc/sysctl.d/50-default.conf",
+            "vm.swappiness = 30\n",
+        );
+        write(
+            &root,
+            "run/sysctl.d/10-early.conf",
+            "vm.swappiness = 1\nkernel.pid_max = 2\n",
+        );
+        write(
+            &root,
+            "usr/lib/sysctl.d/90-late.conf",
+            "kernel.pid_max = 3\n",
+        );
+        write(&root, "usr/lib/sysctl.d/README", "not a fragment");
+        // .conf で終わるディレクトリも読まない
+        write(&root, "etc/sysctl.d/60-stray.conf/x", "not a fragment");
+        write(&root, "etc/sysctl.conf", "net.ipv4.ip_forward = 1\n");
+
+        let loader = Loader::new().root(&root);
+        assert_eq!(
+            loader.fragments().unwrap(),
+            vec![
+                root.join("run/sysctl.d/10-early.conf"),
+                root.join("etc/sysctl.d/50-default.conf"),
+                root.join("usr/lib/sysctl.d/90-late.conf"),
+                root.join("etc/sysctl.conf"),
+            ]
+        );
+        let config = loader.load().unwrap();
+        assert_eq!(config.get("vm.swappiness"), Some("30"));
+        assert_eq!(config.get("kernel.pid_max"), Some("3"));
+        assert_eq!(config.get("net.ipv4.ip_forward"), Some("1"));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_to_dev_null_disables_fragment() {
+        let root = temp_root();
+        write(
+            &root,
+            "usr/lib/sysctl.d/50-coredump.conf",
+            "kernel.core_pattern = |/bin/false\n",
+        );
+        fs::create_dir_all(root.join("etc/sysctl.d")).unwrap();
+        std::os::unix::fs::symlink("/dev/null", root.join("etc/sysctl.d/50-coredump.conf"))
+            .unwrap();
+
+        let config = Loader::new().root(&root).load().unwrap();
+        assert_eq!(config.get("kernel.core_pattern"), None);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn parse_error_names_the_file() {
+        let root = temp_root();
+        write(&root, "etc/sysctl.d/10-broken.conf", "oops\n");
+        let err = Loader::new().root(&root).load().unwrap_err();
+        assert!(matches!(&err, LoadError::Parse { path, .. } if path.ends_with("10-broken.conf")));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
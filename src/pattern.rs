@@ -0,0 +1,257 @@
+// === グロブ ===
+//
+// sysctl のキーに使うグロブ。`*` と `?` はドットをまたがない（1セグメントの中だけにマッチする）。
+// include のファイル名にも同じ書き方を使う（こちらはドットもまたぐ）。
+//
+//   *         0文字以上
+//   ?         1文字
+//   [abc]     括弧内のどれか1文字（[a-z] の範囲、[!a] の否定も使える）
+//   {a,b,c*}  いずれかの候補（候補の中でもグロブを使える）
+
+pub(crate) fn is_glob(key: &str) -> bool {
+    key.contains(['*', '?', '[', '{'])
+}
+
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    expand_braces(pattern)
+        .iter()
+        .any(|alternative| match_alternative(alternative, text))
+}
+
+// ファイル名（パスの最後の要素）同士を比べる
+pub(crate) fn file_name_match(pattern: &str, name: &str) -> bool {
+    let name: Vec<char> = name.chars().collect();
+    expand_braces(pattern).iter().any(|alternative| {
+        let alternative: Vec<char> = alternative.chars().collect();
+        match_from(&alternative, &name, '/')
+    })
+}
+
+// {a,b} を展開した候補のうち text にマッチするものの具体性（specificity の最大値）
+pub(crate) fn match_specificity(pattern: &str, text: &str) -> Option<usize> {
+    expand_braces(pattern)
+        .iter()
+        .filter(|alternative| match_alternative(alternative, text))
+        .map(|alternative| specificity(alternative))
+        .max()
+}
+
+// グロブ以外の文字の数。多いほど具体的なパターン
+fn specificity(alternative: &str) -> usize {
+    let mut count = 0;
+    let mut in_class = false;
+    for c in alternative.chars() {
+        match c {
+            '[' => in_class = true,
+            ']' if in_class => in_class = false,
+            '*' | '?' => {}
+            _ if !in_class => count += 1,
+            _ => {}
+        }
+    }
+    count
+}
+
+// 2つのパターンの両方にマッチするキーがありうるか。
+// セグメントごとに、グロブより前の固定部分と後ろの固定部分が食い違わないかで判断する
+pub(crate) fn may_overlap(a: &str, b: &str) -> bool {
+    let (a, b) = (expand_braces(a), expand_braces(b));
+    a.iter()
+        .any(|x| b.iter().any(|y| alternatives_may_overlap(x, y)))
+}
+
+// 2つのパターンが同じ具体性で重なるか。{a,b} の選択肢の組はすべて比べる
+pub(crate) fn overlap_ties(a: &str, b: &str) -> bool {
+    let (a, b) = (expand_braces(a), expand_braces(b));
+    a.iter()
+        .flat_map(|x| b.iter().map(move |y| (x, y)))
+        .any(|(x, y)| alternatives_may_overlap(x, y) && specificity(x) == specificity(y))
+}
+
+fn alternatives_may_overlap(a: &str, b: &str) -> bool {
+    let a: Vec<&str> = a.split('.').collect();
+    let b: Vec<&str> = b.split('.').collect();
+    a.len() == b.len()
+        && a.iter().zip(&b).all(|(x, y)| {
+            let (x_prefix, x_suffix) = literal_ends(x);
+            let (y_prefix, y_suffix) = literal_ends(y);
+            match (is_glob(x), is_glob(y)) {
+                (false, false) => x == y,
+                (true, false) => match_alternative(x, y),
+                (false, true) => match_alternative(y, x),
+                (true, true) => {
+                    (x_prefix.starts_with(y_prefix) || y_prefix.starts_with(x_prefix))
+                        && (x_suffix.ends_with(y_suffix) || y_suffix.ends_with(x_suffix))
+                }
+            }
+        })
+}
+
+// セグメントの先頭と末尾の、グロブを含まない部分
+fn literal_ends(segment: &str) -> (&str, &str) {
+    let first = segment.find(['*', '?', '[']).unwrap_or(segment.len());
+    let last = segment.rfind(['*', '?', ']']).map_or(0, |i| i + 1);
+    (
+        &segment[..first],
+        &segment[last.max(first).min(segment.len())..],
+    )
+}
+
+// {a,b} を展開する（入れ子は扱わない）。閉じていない { はただの文字
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+    let Some(close) = pattern[open..].find('}').map(|i| open + i) else {
+        return vec![pattern.to_string()];
+    };
+    let (head, body, tail) = (
+        &pattern[..open],
+        &pattern[open + 1..close],
+        &pattern[close + 1..],
+    );
+    body.split(',')
+        .flat_map(|choice| {
+            expand_braces(tail)
+                .into_iter()
+                .map(move |rest| format!("{}{}{}", head, choice, rest))
+        })
+        .collect()
+}
+
+fn match_alternative(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    match_from(&pattern, &text, '.')
+}
+
+// グロブは separator をまたがない
+fn match_from(pattern: &[char], text: &[char], separator: char) -> bool {
+    let rest = |pattern: &[char], text: &[char]| match_from(pattern, text, separator);
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => {
+            // 0文字から、次の区切りの手前までを試す
+            let limit = text
+                .iter()
+                .position(|&c| c == separator)
+                .unwrap_or(text.len());
+            (0..=limit).any(|n| rest(&pattern[1..], &text[n..]))
+        }
+        Some('?') => {
+            matches!(text.first(), Some(&c) if c != separator) && rest(&pattern[1..], &text[1..])
+        }
+        Some('[') => match (parse_class(&pattern[1..]), text.first()) {
+            (Some((matches, len)), Some(&c)) => {
+                c != separator && matches(c) && rest(&pattern[1 + len..], &text[1..])
+            }
+            // 閉じていない [ はただの文字として扱う
+            (None, Some('[')) => rest(&pattern[1..], &text[1..]),
+            _ => false,
+        },
+        Some(&p) => text.first() == Some(&p) && rest(&pattern[1..], &text[1..]),
+    }
+}
+
+// "[" の後ろから "]" までを読み、判定関数と "]" までの文字数を返す
+fn parse_class(pattern: &[char]) -> Option<(impl Fn(char) -> bool, usize)> {
+    let negate = pattern.first() == Some(&'!');
+    let start = usize::from(negate);
+    // 先頭の ] は文字として扱う
+    let end = start + 1 + pattern.get(start + 1..)?.iter().position(|&c| c == ']')?;
+    let body: Vec<char> = pattern[start..end].to_vec();
+    let matches = move |c: char| {
+        let mut i = 0;
+        let mut found = false;
+        while i < body.len() {
+            if i + 2 < body.len() && body[i + 1] == '-' {
+                found |= (body[i]..=body[i + 2]).contains(&c);
+                i += 3;
+            } else {
+                found |= body[i] == c;
+                i += 1;
+            }
+        }
+        found != negate
+    };
+    Some((matches, end + 1))
+}
+
+// === テスト ===
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_matches_within_one_segment() {
+        assert!(glob_match(
+            "net.ipv4.conf.*.rp_filter",
+            "net.ipv4.conf.eth0.rp_filter"
+        ));
+        assert!(glob_match(
+            "net.ipv4.conf.eth*.rp_filter",
+            "net.ipv4.conf.eth.rp_filter"
+        ));
+        assert!(!glob_match(
+            "net.ipv4.conf.*.rp_filter",
+            "net.ipv4.conf.eth0.100.rp_filter"
+        ));
+        assert!(!glob_match("net.*", "net.ipv4.ip_forward"));
+    }
+
+    #[test]
+    fn question_mark_and_classes() {
+        assert!(glob_match(
+            "net.ipv4.conf.eth?.rp_filter",
+            "net.ipv4.conf.eth1.rp_filter"
+        ));
+        assert!(glob_match(
+            "net.ipv4.conf.eth[0-2].rp_filter",
+            "net.ipv4.conf.eth2.rp_filter"
+        ));
+        assert!(!glob_match(
+            "net.ipv4.conf.eth[!0-2].rp_filter",
+            "net.ipv4.conf.eth2.rp_filter"
+        ));
+        assert!(glob_match("a.[].b", "a.[].b"));
+        assert!(!is_glob("net.ipv4.ip_forward"));
+    }
+
+    #[test]
+    fn braces_expand_to_alternatives() {
+        let pattern = "net.ipv6.conf.{all,default,eth*}.disable_ipv6";
+        assert!(glob_match(pattern, "net.ipv6.conf.all.disable_ipv6"));
+        assert!(glob_match(pattern, "net.ipv6.conf.eth0.disable_ipv6"));
+        assert!(!glob_match(pattern, "net.ipv6.conf.lo.disable_ipv6"));
+        assert_eq!(
+            match_specificity(pattern, "net.ipv6.conf.default.disable_ipv6"),
+            Some(34)
+        );
+        assert_eq!(
+            match_specificity(pattern, "net.ipv6.conf.eth0.disable_ipv6"),
+            Some(30)
+        );
+    }
+
+    #[test]
+    fn overlap_is_decided_per_segment() {
+        assert!(may_overlap(
+            "net.ipv4.conf.*.rp_filter",
+            "net.ipv4.conf.eth*.rp_filter"
+        ));
+        assert!(may_overlap(
+            "net.ipv4.conf.*.rp_filter",
+            "net.*.conf.lo.rp_filter"
+        ));
+        assert!(!may_overlap(
+            "net.ipv4.conf.eth*.rp_filter",
+            "net.ipv4.conf.wlan*.rp_filter"
+        ));
+        assert!(!may_overlap(
+            "net.ipv4.conf.*.rp_filter",
+            "net.ipv4.conf.*.forwarding"
+        ));
+        assert!(!may_overlap("a.*", "a.b.c"));
+    }
+}
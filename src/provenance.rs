@@ -0,0 +1,123 @@
+use std::fmt;
+
+use crate::{Config, Entry, normalize_key};
+
+// === 由来 ===
+//
+// 「なぜこの値になっているのか」を答えるため、キーのすべての定義と有効なものを示す。
+//
+//   vm.swappiness = 10
+//     /usr/lib/sysctl.d/50-default.conf:3: 60 (overridden)
+//     /etc/sysctl.d/99-local.conf:1: 10 (effective)
+
+#[derive(Debug)]
+pub struct Explanation<'a> {
+    key: &'a str,
+    // 適用順
+    definitions: Vec<&'a Entry>,
+    winner: usize,
+}
+
+impl<'a> Explanation<'a> {
+    pub fn key(&self) -> &'a str {
+        self.key
+    }
+
+    pub fn definitions(&self) -> &[&'a Entry] {
+        &self.definitions
+    }
+
+    pub fn winner(&self) -> &'a Entry {
+        self.definitions[self.winner]
+    }
+}
+
+impl fmt::Display for Explanation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} = {}", self.key, self.winner().value())?;
+        for (i, entry) in self.definitions.iter().enumerate() {
+            let source = entry
+                .source()
+                .map_or("<input>".to_string(), |path| path.display().to_string());
+            let status = if i == self.winner {
+                "effective"
+            } else {
+                "overridden"
+            };
+            writeln!(
+                f,
+                "  {}:{}: {} ({})",
+                source,
+                entry.line_number(),
+                entry.value(),
+                status
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    pub fn explain(&self, key: &str) -> Option<Explanation<'_>> {
+        let (key, history) = self.history.get_key_value(normalize_key(key).as_ref())?;
+        let effective = self.entry(key)?;
+        let winner = history.iter().rposition(|entry| entry == effective)?;
+        Some(Explanation {
+            key,
+            definitions: history.iter().collect(),
+            winner,
+        })
+    }
+}
+
+// === テスト ===
+
+#[cfg(test)]
+mod tests {
+    use crate::{Config, DuplicatePolicy, ParseOptions, tokenize};
+    use std::path::Path;
+
+    fn parse_from(path: &str, content: &str) -> Config {
+        let options = ParseOptions::default();
+        let mut config = Config::default();
+        let tokens = tokenize(content, &options).unwrap();
+        config
+            .absorb(tokens, &options, Some(Path::new(path)))
+            .unwrap();
+        config
+    }
+
+    #[test]
+    fn explain_lists_every_definition_across_files() {
+        let mut config = parse_from("/usr/lib/sysctl.d/50-default.conf", "vm.swappiness = 60\n");
+        config.merge(parse_from(
+            "/etc/sysctl.d/99-local.conf",
+            "# tuned for databases\nvm.swappiness = 10\n",
+        ));
+
+        let explanation = config.explain("vm.swappiness").unwrap();
+        assert_eq!(explanation.definitions().len(), 2);
+        assert_eq!(explanation.winner().value(), "10");
+        assert_eq!(
+            explanation.to_string(),
+            "\
+vm.swappiness = 10
+  /usr/lib/sysctl.d/50-default.conf:1: 60 (overridden)
+  /etc/sysctl.d/99-local.conf:2: 10 (effective)
+"
+        );
+        assert!(config.explain("kernel.pid_max").is_none());
+    }
+
+    #[test]
+    fn explain_follows_duplicate_policy_within_a_file() {
+        let options = ParseOptions {
+            duplicates: DuplicatePolicy::FirstWins,
+            ..ParseOptions::default()
+        };
+        let config = Config::parse_with("debug = true\ndebug = false", &options).unwrap();
+        let explanation = config.explain("debug").unwrap();
+        assert_eq!(explanation.definitions().len(), 2);
+        assert_eq!(explanation.winner().line_number(), 1);
+    }
+}
@@ -0,0 +1,125 @@
+// === 引用符付きの値 ===
+//
+// ParseOptions::quoted_values を有効にしたときだけ使う。
+// "..." と '...' のどちらでも次のエスケープが使える。
+//
+//   \n  \t  \\  \"  \'  \u{1F600}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum QuoteError {
+    // 閉じる引用符がない
+    Unterminated,
+    // at..at+len が不正なエスケープ（text 内のバイト位置）
+    InvalidEscape { at: usize, len: usize },
+    // 閉じる引用符の後ろに空白以外がある
+    Trailing { at: usize },
+}
+
+pub(crate) fn is_quoted(text: &str) -> bool {
+    text.starts_with(['"', '\''])
+}
+
+// text は引用符で始まっていること
+pub(crate) fn unquote(text: &str) -> Result<String, QuoteError> {
+    let quote = text.chars().next().unwrap();
+    let mut value = String::new();
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            let rest = &text[i + 1..];
+            return match rest.find(|c: char| !c.is_whitespace()) {
+                Some(j) => Err(QuoteError::Trailing { at: i + 1 + j }),
+                None => Ok(value),
+            };
+        }
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        let invalid = |end: usize| QuoteError::InvalidEscape {
+            at: i,
+            len: end - i,
+        };
+        let (j, escaped) = chars.next().ok_or(QuoteError::Unterminated)?;
+        match escaped {
+            'n' => value.push('\n'),
+            't' => value.push('\t'),
+            '\\' | '"' | '\'' => value.push(escaped),
+            'u' => {
+                let body = text[j + 1..].strip_prefix('{').ok_or(invalid(j + 1))?;
+                let close = body.find('}').ok_or(invalid(j + 1))?;
+                let end = j + 2 + close + 1;
+                let c = u32::from_str_radix(&body[..close], 16)
+                    .ok()
+                    .filter(|_| (1..=6).contains(&close))
+                    .and_then(char::from_u32)
+                    .ok_or(invalid(end))?;
+                value.push(c);
+                // "{...}" の分を読み飛ばす
+                while chars.next_if(|&(k, _)| k < end).is_some() {}
+            }
+            _ => return Err(invalid(j + escaped.len_utf8())),
+        }
+    }
+    Err(QuoteError::Unterminated)
+}
+
+// そのまま書くとパースし直したときに同じ値にならないもの
+pub(crate) fn needs_quotes(value: &str) -> bool {
+    value != value.trim() || is_quoted(value) || value.contains(['\n', '\r'])
+}
+
+pub(crate) fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            c if c.is_control() => quoted.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// === テスト ===
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn escapes_are_decoded() {
+        assert_eq!(unquote(r#""  a # b  ""#), Ok("  a # b  ".to_string()));
+        assert_eq!(
+            unquote(r#"'line1\nline2\t\\ \" \''"#),
+            Ok("line1\nline2\t\\ \" '".to_string())
+        );
+        assert_eq!(unquote(r#""\u{1F600}\u{e9}""#), Ok("😀é".to_string()));
+    }
+
+    #[test]
+    fn broken_quotes_are_reported_with_position() {
+        assert_eq!(unquote(r#""abc"#), Err(QuoteError::Unterminated));
+        assert_eq!(
+            unquote(r#""a\qb""#),
+            Err(QuoteError::InvalidEscape { at: 2, len: 2 })
+        );
+        assert_eq!(
+            unquote(r#""\u{zz}""#),
+            Err(QuoteError::InvalidEscape { at: 1, len: 6 })
+        );
+        assert_eq!(unquote(r#""a" b"#), Err(QuoteError::Trailing { at: 4 }));
+    }
+
+    proptest! {
+        #[test]
+        fn quote_then_unquote_is_identity(value in any::<String>()) {
+            prop_assert_eq!(unquote(&quote(&value)), Ok(value));
+        }
+    }
+}
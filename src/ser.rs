@@ -0,0 +1,687 @@
+use std::fmt;
+
+use serde::ser::{self, Serialize};
+
+use crate::de::join;
+use crate::quote::{needs_quotes, quote};
+use crate::{ParseOptions, Token, normalize_key, tokenize_line};
+
+// === serde によるシリアライズ ===
+//
+// feature = "serde" のときだけ使える。構造体やマップをドット区切りの key = value の行にする。
+//
+//   struct Log { file: PathBuf }        →  log.file = /var/log/console.log
+//   conf: BTreeMap<String, Iface>       →  net.ipv4.conf.eth0.rp_filter = 1
+//
+// 構造体のフィールドは定義順、マップのエントリはキーの順に書くので、同じ値からはいつも同じ出力になる。
+// None のフィールドは書かない。並び（Vec やタプル）は空白で区切った1つの値になる。
+// 書いた行はすべて読み直して確かめ、同じキーと値に戻らなければエラーにする。
+
+// === エラー型 ===
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SerError {
+    // 値として書けないもの（入れ子の並び、データを持つ enum のバリアントなど）。key が空ならトップレベル
+    Unsupported { key: String, what: String },
+    // キーの書式に合わない（マップのキーに . を含むなど）
+    InvalidKey { key: String },
+    // 読み直すと同じ値にならない（前後の空白や改行を含み、引用符も使えないときなど）
+    InvalidValue { key: String, value: String },
+    // Serialize の実装が返したエラー
+    Custom(String),
+}
+
+impl fmt::Display for SerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerError::Unsupported { key, what } if key.is_empty() => {
+                write!(f, "cannot write {} at the top level", what)
+            }
+            SerError::Unsupported { key, what } => {
+                write!(f, "'{}': cannot write {} as a value", key, what)
+            }
+            SerError::InvalidKey { key } => write!(f, "'{}': not a valid key", key),
+            SerError::InvalidValue { key, value } => {
+                write!(
+                    f,
+                    "'{}': '{}' would not read back as the same value",
+                    key, value
+                )
+            }
+            SerError::Custom(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SerError {}
+
+impl ser::Error for SerError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        SerError::Custom(message.to_string())
+    }
+}
+
+impl SerError {
+    // 入れ子の値で起きたエラーのキーを、外側から見たキーにする
+    fn nested(self, segment: &str) -> Self {
+        match self {
+            SerError::Unsupported { key, what } => SerError::Unsupported {
+                key: join(segment, &key),
+                what,
+            },
+            SerError::InvalidKey { key } => SerError::InvalidKey {
+                key: join(segment, &key),
+            },
+            error => error,
+        }
+    }
+}
+
+// === 先頭の - ===
+
+const IGNORE_ERROR: &str = "$toy_sysctl_conf::ignore_error";
+
+// #[serde(serialize_with = "toy_sysctl_conf::ignore_error")] を付けたフィールドは - 付きで書く。
+// 他のシリアライザからはただの newtype に見える
+pub fn ignore_error<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize + ?Sized,
+    S: ser::Serializer,
+{
+    serializer.serialize_newtype_struct(IGNORE_ERROR, value)
+}
+
+// === Serializer ===
+
+#[derive(Debug, Clone, Default)]
+pub struct Serializer {
+    header: Vec<String>,
+    // 出力を読むときのオプション。quoted_values なら必要な値に引用符を付け、keys でキーを確かめる
+    options: ParseOptions,
+}
+
+impl Serializer {
+    pub fn new() -> Self {
+        Serializer::default()
+    }
+
+    // 先頭に # のコメントとして書く行。呼んだ順に並ぶ。改行を含めば複数行のコメントになる
+    pub fn header(mut self, line: &str) -> Self {
+        self.header.push(line.to_string());
+        self
+    }
+
+    pub fn options(mut self, options: &ParseOptions) -> Self {
+        self.options = options.clone();
+        self
+    }
+
+    pub fn to_string<T: Serialize + ?Sized>(&self, value: &T) -> Result<String, SerError> {
+        let mut out = String::new();
+        // 改行を含む行は分けて、それぞれをコメントにする。そのまま書くと設定の行として読まれる
+        let header = self
+            .header
+            .iter()
+            .flat_map(|line| line.split("\r\n"))
+            .flat_map(|line| line.split(['\n', '\r']));
+        for line in header {
+            if line.is_empty() {
+                out.push_str("#\n");
+            } else {
+                out.push_str(&format!("# {}\n", line));
+            }
+        }
+        if !self.header.is_empty() {
+            out.push('\n');
+        }
+        for line in value.serialize(ValueSerializer)? {
+            if line.key.is_empty() {
+                return Err(SerError::Unsupported {
+                    key: String::new(),
+                    what: "a value without a key".to_string(),
+                });
+            }
+            out.push_str(&self.write(&line)?);
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    // Document::checked_token と同じく、書いた行を読み直して確かめる
+    fn write(&self, line: &Line) -> Result<String, SerError> {
+        // 最初のセグメントが / を含むと、読み直したときに / 区切りのキーとみなされる
+        if self.options.keys.check(&line.key).is_err() || normalize_key(&line.key) != line.key {
+            return Err(SerError::InvalidKey {
+                key: line.key.clone(),
+            });
+        }
+        let value = if self.options.quoted_values && needs_quotes(&line.value) {
+            quote(&line.value)
+        } else {
+            line.value.clone()
+        };
+        let prefix = if line.ignore_error { "-" } else { "" };
+        let raw = format!("{}{} = {}", prefix, line.key, value);
+        let raw = raw.trim_end().to_string();
+        let reads_back = !value.contains(['\n', '\r'])
+            && matches!(
+                tokenize_line(&raw, 1, 0, &self.options),
+                Ok(Token::KeyValue { key, value, .. })
+                    if *key == line.key && value == line.value
+            );
+        if !reads_back {
+            return Err(SerError::InvalidValue {
+                key: line.key.clone(),
+                value: line.value.clone(),
+            });
+        }
+        Ok(raw)
+    }
+}
+
+// Serializer::new() で書く
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, SerError> {
+    Serializer::new().to_string(value)
+}
+
+// 値を平らにした1行。key は値を書いた位置から見たもの（値そのものなら空）
+#[derive(Debug)]
+struct Line {
+    key: String,
+    value: String,
+    ignore_error: bool,
+}
+
+fn scalar(value: impl ToString) -> Vec<Line> {
+    vec![Line {
+        key: String::new(),
+        value: value.to_string(),
+        ignore_error: false,
+    }]
+}
+
+fn unsupported(what: &str) -> SerError {
+    SerError::Unsupported {
+        key: String::new(),
+        what: what.to_string(),
+    }
+}
+
+// segment の下にある値の行にする
+fn nest(segment: &str, lines: Vec<Line>) -> Vec<Line> {
+    lines
+        .into_iter()
+        .map(|line| Line {
+            key: join(segment, &line.key),
+            ..line
+        })
+        .collect()
+}
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Vec<Line>;
+    type Error = SerError;
+    type SerializeSeq = Words;
+    type SerializeTuple = Words;
+    type SerializeTupleStruct = Words;
+    type SerializeTupleVariant = ser::Impossible<Vec<Line>, SerError>;
+    type SerializeMap = Table;
+    type SerializeStruct = Table;
+    type SerializeStructVariant = ser::Impossible<Vec<Line>, SerError>;
+
+    fn serialize_bool(self, v: bool) -> Result<Vec<Line>, SerError> {
+        Ok(scalar(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Vec<Line>, SerError> {
+        Ok(scalar(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Vec<Line>, SerError> {
+        Ok(scalar(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Vec<Line>, SerError> {
+        Ok(scalar(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Vec<Line>, SerError> {
+        Ok(scalar(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Vec<Line>, SerError> {
+        Ok(scalar(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Vec<Line>, SerError> {
+        Ok(scalar(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Vec<Line>, SerError> {
+        Ok(scalar(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Vec<Line>, SerError> {
+        Ok(scalar(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Vec<Line>, SerError> {
+        Ok(scalar(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Vec<Line>, SerError> {
+        Ok(scalar(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Vec<Line>, SerError> {
+        Ok(scalar(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Vec<Line>, SerError> {
+        Ok(scalar(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Vec<Line>, SerError> {
+        std::str::from_utf8(v)
+            .map(scalar)
+            .map_err(|_| unsupported("bytes that are not UTF-8"))
+    }
+
+    fn serialize_none(self) -> Result<Vec<Line>, SerError> {
+        Ok(Vec::new())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Vec<Line>, SerError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Vec<Line>, SerError> {
+        Ok(Vec::new())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Vec<Line>, SerError> {
+        Ok(Vec::new())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Vec<Line>, SerError> {
+        Ok(scalar(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Vec<Line>, SerError> {
+        let mut lines = value.serialize(self)?;
+        if name == IGNORE_ERROR {
+            for line in &mut lines {
+                line.ignore_error = true;
+            }
+        }
+        Ok(lines)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _value: &T,
+    ) -> Result<Vec<Line>, SerError> {
+        Err(unsupported(&format!(
+            "enum variant '{}' with data",
+            variant
+        )))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Words, SerError> {
+        Ok(Words(Vec::new()))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Words, SerError> {
+        Ok(Words(Vec::new()))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Words, SerError> {
+        Ok(Words(Vec::new()))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerError> {
+        Err(unsupported(&format!(
+            "enum variant '{}' with data",
+            variant
+        )))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Table, SerError> {
+        Ok(Table::default())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Table, SerError> {
+        Ok(Table::default())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerError> {
+        Err(unsupported(&format!(
+            "enum variant '{}' with data",
+            variant
+        )))
+    }
+}
+
+// 並びの要素。空白で区切って1つの値にする
+struct Words(Vec<String>);
+
+impl Words {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        match value.serialize(ValueSerializer)?.as_slice() {
+            [line] if line.key.is_empty() => {
+                // 空白を含む要素は読み直すと分かれてしまう
+                if line.value.is_empty() || line.value.contains(char::is_whitespace) {
+                    return Err(unsupported(&format!("list element '{}'", line.value)));
+                }
+                self.0.push(line.value.clone());
+                Ok(())
+            }
+            _ => Err(unsupported("a list of tables or lists")),
+        }
+    }
+}
+
+impl ser::SerializeSeq for Words {
+    type Ok = Vec<Line>;
+    type Error = SerError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Vec<Line>, SerError> {
+        Ok(scalar(self.0.join(" ")))
+    }
+}
+
+impl ser::SerializeTuple for Words {
+    type Ok = Vec<Line>;
+    type Error = SerError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Vec<Line>, SerError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for Words {
+    type Ok = Vec<Line>;
+    type Error = SerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Vec<Line>, SerError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+// 構造体とマップ。マップのエントリだけは end でキーの順に並べ替える
+#[derive(Default)]
+struct Table {
+    entries: Vec<(String, Vec<Line>)>,
+    key: Option<String>,
+}
+
+impl Table {
+    fn push<T: Serialize + ?Sized>(&mut self, segment: String, value: &T) -> Result<(), SerError> {
+        let lines = value
+            .serialize(ValueSerializer)
+            .map_err(|e| e.nested(&segment))?;
+        self.entries.push((segment, lines));
+        Ok(())
+    }
+
+    fn end(self) -> Vec<Line> {
+        self.entries
+            .into_iter()
+            .flat_map(|(segment, lines)| nest(&segment, lines))
+            .collect()
+    }
+}
+
+impl ser::SerializeMap for Table {
+    type Ok = Vec<Line>;
+    type Error = SerError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerError> {
+        let segment = match key.serialize(ValueSerializer)?.as_slice() {
+            [line] if line.key.is_empty() => line.value.clone(),
+            _ => return Err(unsupported("a map key that is not a string")),
+        };
+        // . を含むキーは読み直すと別の入れ子になる。/ はセグメントの中の . を表す正規の形（eth0/100）
+        if segment.is_empty() || segment.contains('.') {
+            return Err(SerError::InvalidKey { key: segment });
+        }
+        self.key = Some(segment);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        let segment = self
+            .key
+            .take()
+            .ok_or_else(|| <SerError as ser::Error>::custom("value written before its key"))?;
+        self.push(segment, value)
+    }
+
+    fn end(mut self) -> Result<Vec<Line>, SerError> {
+        self.entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(Table::end(self))
+    }
+}
+
+impl ser::SerializeStruct for Table {
+    type Ok = Vec<Line>;
+    type Error = SerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), SerError> {
+        self.push(name.to_string(), value)
+    }
+
+    fn end(self) -> Result<Vec<Line>, SerError> {
+        Ok(Table::end(self))
+    }
+}
+
+// === テスト ===
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, from_config};
+    use serde::{Deserialize, Serialize};
+    use std::collections::{BTreeMap, HashMap};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Host {
+        vm: Vm,
+        net: Net,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        motd: Option<String>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Vm {
+        swappiness: u8,
+        #[serde(serialize_with = "ignore_error")]
+        overcommit_memory: i64,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Net {
+        ipv4: Ipv4,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Ipv4 {
+        ip_forward: bool,
+        ip_local_port_range: (u16, u16),
+        conf: HashMap<String, Iface>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Iface {
+        rp_filter: i64,
+    }
+
+    fn host() -> Host {
+        let conf = [("eth1", 2), ("all", 1), ("eth0", 0)]
+            .into_iter()
+            .map(|(name, rp_filter)| (name.to_string(), Iface { rp_filter }))
+            .collect();
+        Host {
+            vm: Vm {
+                swappiness: 10,
+                overcommit_memory: 1,
+            },
+            net: Net {
+                ipv4: Ipv4 {
+                    ip_forward: true,
+                    ip_local_port_range: (32768, 60999),
+                    conf,
+                },
+            },
+            motd: None,
+        }
+    }
+
+    #[test]
+    fn structs_and_maps_become_dotted_lines_in_a_stable_order() {
+        let text = Serializer::new()
+            .header("generated for web01")
+            .to_string(&host())
+            .unwrap();
+        assert_eq!(
+            text,
+            "\
+# generated for web01
+
+vm.swappiness = 10
+-vm.overcommit_memory = 1
+net.ipv4.ip_forward = true
+net.ipv4.ip_local_port_range = 32768 60999
+net.ipv4.conf.all.rp_filter = 1
+net.ipv4.conf.eth0.rp_filter = 0
+net.ipv4.conf.eth1.rp_filter = 2
+"
+        );
+        let config = Config::parse(&text).unwrap();
+        assert!(config.entry("vm.overcommit_memory").unwrap().ignore_error());
+        assert_eq!(from_config::<Host>(&config).unwrap(), host());
+
+        // ヘッダーの改行から設定の行は書けない
+        let text = Serializer::new()
+            .header("x\nvm.swappiness = 100\r\n\ry")
+            .to_string(&host())
+            .unwrap();
+        assert!(text.starts_with("# x\n# vm.swappiness = 100\n#\n# y\n\nvm.swappiness = 10\n"));
+        let config = Config::parse(&text).unwrap();
+        assert_eq!(config.get("vm.swappiness"), Some("10"));
+    }
+
+    #[test]
+    fn vlan_interfaces_round_trip() {
+        let text = "\
+vm.swappiness = 10
+vm.overcommit_memory = 1
+net.ipv4.ip_forward = true
+net.ipv4.ip_local_port_range = 32768 60999
+net/ipv4/conf/eth0.100/rp_filter = 2
+net.ipv4.conf.eth0.rp_filter = 1
+";
+        let host: Host = from_config(&Config::parse(text).unwrap()).unwrap();
+        assert_eq!(host.net.ipv4.conf["eth0/100"], Iface { rp_filter: 2 });
+        let written = to_string(&host).unwrap();
+        assert!(written.contains("net.ipv4.conf.eth0/100.rp_filter = 2\n"));
+        assert_eq!(
+            from_config::<Host>(&Config::parse(&written).unwrap()).unwrap(),
+            host
+        );
+    }
+
+    #[test]
+    fn lines_that_would_not_read_back_are_errors() {
+        let mut host = host();
+        host.motd = Some(" hello\n".to_string());
+        assert_eq!(
+            to_string(&host),
+            Err(SerError::InvalidValue {
+                key: "motd".to_string(),
+                value: " hello\n".to_string()
+            })
+        );
+        // 引用符を使えるなら書ける
+        let options = ParseOptions {
+            quoted_values: true,
+            ..ParseOptions::default()
+        };
+        let text = Serializer::new()
+            .options(&options)
+            .to_string(&host)
+            .unwrap();
+        let config = Config::parse_with(&text, &options).unwrap();
+        assert_eq!(config.get("motd"), Some(" hello\n"));
+
+        let mut host = self::host();
+        host.net
+            .ipv4
+            .conf
+            .insert("eth0.100".to_string(), Iface { rp_filter: 1 });
+        assert_eq!(
+            to_string(&host),
+            Err(SerError::InvalidKey {
+                key: "net.ipv4.conf.eth0.100".to_string()
+            })
+        );
+        // / で始まるキーは / 区切りとして読まれる
+        let slashed: BTreeMap<&str, i64> = [("eth0/100", 1)].into_iter().collect();
+        assert!(matches!(
+            to_string(&slashed),
+            Err(SerError::InvalidKey { key }) if key == "eth0/100"
+        ));
+        let bad_segment: BTreeMap<&str, i64> = [("a b", 1)].into_iter().collect();
+        assert!(matches!(
+            to_string(&bad_segment),
+            Err(SerError::InvalidKey { .. })
+        ));
+        assert!(matches!(
+            to_string(&3),
+            Err(SerError::Unsupported { key, .. }) if key.is_empty()
+        ));
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+use std::io::{self, BufRead};
+
+use crate::{
+    Config, ParseError, ParseOptions, Token, continuation, is_comment, split_newline, tokenize_line,
+};
+
+// === ストリーム ===
+//
+// 入力全体を文字列として持たずに、BufRead から1行（継続行はまとめて）ずつ Token にする。
+// 保持するのは読みかけの行だけなので、何百 MB の sysctl -a のダンプでも使える。
+
+#[derive(Debug)]
+pub enum StreamError {
+    // line_number 行目を読んでいるときの I/O エラー（不正な UTF-8 を含む）
+    Io {
+        line_number: usize,
+        error: io::Error,
+    },
+    Parse(ParseError),
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::Io { line_number, error } => write!(f, "line {}: {}", line_number, error),
+            StreamError::Parse(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StreamError::Io { error, .. } => Some(error),
+            StreamError::Parse(error) => Some(error),
+        }
+    }
+}
+
+impl From<ParseError> for StreamError {
+    fn from(error: ParseError) -> Self {
+        StreamError::Parse(error)
+    }
+}
+
+// ParseError の後も次の行から読み続ける。I/O エラーの後は None を返す
+pub struct Tokens<R> {
+    reader: R,
+    options: ParseOptions,
+    // 次に読む行の行番号と、その行頭のバイト位置
+    line_number: usize,
+    offset: usize,
+    buf: String,
+    done: bool,
+}
+
+impl<R: BufRead> Tokens<R> {
+    pub fn new(reader: R, options: &ParseOptions) -> Self {
+        Tokens {
+            reader,
+            options: options.clone(),
+            line_number: 1,
+            offset: 0,
+            buf: String::new(),
+            done: false,
+        }
+    }
+
+    // 継続行をまとめて buf に読み込み、読んだ行数を返す（0 なら入力の終わり）
+    fn read_entry(&mut self) -> io::Result<usize> {
+        self.buf.clear();
+        let mut lines = 0;
+        let mut joinable = false;
+        loop {
+            let start = self.buf.len();
+            if self.reader.read_line(&mut self.buf)? == 0 {
+                return Ok(lines);
+            }
+            let line = self.buf[start..].trim_end_matches(['\n', '\r']);
+            if lines == 0 {
+                let trimmed = line.trim();
+                joinable =
+                    self.options.line_continuation && !trimmed.is_empty() && !is_comment(trimmed);
+            }
+            lines += 1;
+            if !(joinable && continuation(line).is_some()) {
+                return Ok(lines);
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Tokens<R> {
+    type Item = Result<Token, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let lines = match self.read_entry() {
+            Ok(0) => {
+                self.done = true;
+                return None;
+            }
+            Ok(lines) => lines,
+            Err(error) => {
+                self.done = true;
+                return Some(Err(StreamError::Io {
+                    line_number: self.line_number,
+                    error,
+                }));
+            }
+        };
+        let raw = split_newline(&self.buf).0;
+        let token = tokenize_line(raw, self.line_number, self.offset, &self.options);
+        self.line_number += lines;
+        self.offset += self.buf.len();
+        Some(token.map_err(StreamError::Parse))
+    }
+}
+
+impl Config {
+    // Config::parse_with と同じ結果を、入力全体を読み込まずに得る
+    pub fn from_reader<R: BufRead>(
+        reader: R,
+        options: &ParseOptions,
+    ) -> Result<Config, StreamError> {
+        let mut config = Config::default();
+        for token in Tokens::new(reader, options) {
+            config.absorb([token?], options, None)?;
+        }
+        Ok(config)
+    }
+}
+
+// === テスト ===
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::arb_config_content;
+    use crate::tokenize;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn streamed_tokens_match_tokenize(content in arb_config_content(), crlf in any::<bool>()) {
+            let content = if crlf { content.replace('\n', "\r\n") } else { content };
+            let options = ParseOptions::default();
+            let streamed: Vec<Token> = Tokens::new(content.as_bytes(), &options)
+                .collect::<Result<_, _>>()
+                .unwrap();
+            prop_assert_eq!(streamed, tokenize(&content, &options).unwrap());
+        }
+    }
+
+    #[test]
+    fn reader_keeps_going_after_parse_errors_and_joins_continuations() {
+        let content = "a = 1 \\\n  2\noops\nb = 3";
+        let options = ParseOptions {
+            line_continuation: true,
+            ..ParseOptions::default()
+        };
+        let results: Vec<_> = Tokens::new(content.as_bytes(), &options).collect();
+        assert_eq!(results.len(), 3);
+        assert!(matches!(
+            &results[0],
+            Ok(Token::KeyValue { value, .. }) if value == "1 2"
+        ));
+        assert!(matches!(
+            &results[1],
+            Err(StreamError::Parse(ParseError::InvalidLine {
+                line_number: 3,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            &results[2],
+            Ok(Token::KeyValue { key_span, .. }) if key_span.line == 4 && &content[key_span.range()] == "b"
+        ));
+    }
+
+    #[test]
+    fn invalid_utf8_is_an_io_error_with_its_line() {
+        let content: &[u8] = b"a = 1\nb = \xff\n";
+        let err = Config::from_reader(content, &ParseOptions::default()).unwrap_err();
+        assert!(matches!(err, StreamError::Io { line_number: 2, .. }));
+        let config = Config::from_reader("a = 1\n".as_bytes(), &ParseOptions::default()).unwrap();
+        assert_eq!(config.get("a"), Some("1"));
+    }
+}
@@ -0,0 +1,99 @@
+// === "did you mean" 候補 ===
+//
+// キー全体の編集距離に加えて、ドット区切りのセグメント単位でも比べる。
+// 例: log.flie → log.file（1セグメントだけ近い）、file.log → log.file（並びが違うだけ）
+
+// 似ているものから順に最大 3 件
+pub(crate) fn similar_keys<'a, I>(key: &str, candidates: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let mut scored: Vec<(usize, &String)> = candidates
+        .into_iter()
+        .filter_map(|candidate| Some((similarity(key, candidate)?, candidate)))
+        .collect();
+    scored.sort();
+    scored.into_iter().take(3).map(|(_, c)| c.clone()).collect()
+}
+
+// 小さいほど似ている。似ていなければ None
+fn similarity(key: &str, candidate: &str) -> Option<usize> {
+    if key == candidate {
+        return None;
+    }
+    let distance = levenshtein(key, candidate);
+    if distance <= threshold(key.len().max(candidate.len()), 4) {
+        return Some(distance);
+    }
+
+    let a: Vec<&str> = key.split('.').collect();
+    let b: Vec<&str> = candidate.split('.').collect();
+    if a.len() != b.len() {
+        return None;
+    }
+    let differing: Vec<(&str, &str)> = a
+        .iter()
+        .zip(&b)
+        .filter(|(x, y)| x != y)
+        .map(|(x, y)| (*x, *y))
+        .collect();
+    if let [(x, y)] = differing[..] {
+        let distance = levenshtein(x, y);
+        if distance <= threshold(x.len().max(y.len()), 3) {
+            return Some(distance);
+        }
+    }
+
+    let (mut sorted_a, mut sorted_b) = (a, b);
+    sorted_a.sort_unstable();
+    sorted_b.sort_unstable();
+    (sorted_a == sorted_b).then_some(1)
+}
+
+fn threshold(len: usize, divisor: usize) -> usize {
+    (len / divisor).max(1)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(ca != *cb);
+            current.push(substitute.min(prev[j + 1] + 1).min(current[j] + 1));
+        }
+        prev = current;
+    }
+    prev[b.len()]
+}
+
+// === テスト ===
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn typo_and_swapped_segments_are_suggested() {
+        let schema = keys(&["log.file", "log.name", "debug", "net.ipv4.ip_forward"]);
+        assert_eq!(similar_keys("log.flie", &schema), vec!["log.file"]);
+        assert_eq!(similar_keys("file.log", &schema), vec!["log.file"]);
+        assert_eq!(
+            similar_keys("net.ipv4.ip_froward", &schema),
+            vec!["net.ipv4.ip_forward"]
+        );
+        assert!(similar_keys("endpoint", &schema).is_empty());
+    }
+
+    #[test]
+    fn levenshtein_counts_edits() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("debug", "debug"), 0);
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::num::IntErrorKind;
+use std::str::FromStr;
+
+use crate::{Config, Schema, Span, ValidationError, ValueType, normalize_key, validate};
+
+// === 型付きの取得 ===
+//
+// Config の値を bool や整数として取り出す。受け付ける書き方は ValueType::is_valid と同じなので、
+// validate を通った値はかならず取得でき、取得できない値はかならず検証エラーになる。
+//
+//   bool    — true / false
+//   整数    — 省略可能な符号と10進の数字（i64 の範囲外は OutOfRange）
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Str(String),
+    Bool(bool),
+    Integer(i64),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Integer(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+// === エラー型 ===
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GetError {
+    Missing {
+        key: String,
+    },
+    // expected は型の名前（"bool"、"integer"、get_as なら Rust の型名）。span は値を指す
+    Invalid {
+        key: String,
+        expected: String,
+        value: String,
+        span: Span,
+    },
+    // 整数として書けているが expected の範囲に収まらない
+    OutOfRange {
+        key: String,
+        expected: String,
+        value: String,
+        span: Span,
+    },
+}
+
+impl fmt::Display for GetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GetError::Missing { key } => write!(f, "'{}': missing", key),
+            GetError::Invalid {
+                key,
+                expected,
+                value,
+                span,
+            } => write!(
+                f,
+                "line {}: '{}': expected {}, got '{}'",
+                span.line, key, expected, value
+            ),
+            GetError::OutOfRange {
+                key,
+                expected,
+                value,
+                span,
+            } => write!(
+                f,
+                "line {}: '{}': '{}' is out of range for {}",
+                span.line, key, value, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GetError {}
+
+// ValueType::parse の失敗。キーと位置は GetError にするときに足す
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ValueError {
+    Invalid,
+    OutOfRange,
+}
+
+impl ValueType {
+    pub(crate) fn parse(&self, value: &str) -> Result<Value, ValueError> {
+        match self {
+            ValueType::Str => Ok(Value::Str(value.to_string())),
+            ValueType::Bool => parse_bool(value).map(Value::Bool),
+            ValueType::Integer => parse_integer(value).map(Value::Integer),
+        }
+    }
+}
+
+pub(crate) fn parse_bool(value: &str) -> Result<bool, ValueError> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(ValueError::Invalid),
+    }
+}
+
+// 書き方は i64 と同じで判定し、範囲だけを T で決める（"-1" は u64 でも書き方は正しい）
+pub(crate) fn parse_integer<T: TryFrom<i128>>(value: &str) -> Result<T, ValueError> {
+    let n = value.parse::<i128>().map_err(|error| match error.kind() {
+        IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => ValueError::OutOfRange,
+        _ => ValueError::Invalid,
+    })?;
+    T::try_from(n).map_err(|_| ValueError::OutOfRange)
+}
+
+impl Config {
+    pub fn get_bool(&self, key: &str) -> Result<bool, GetError> {
+        self.get_with(key, "bool", parse_bool)
+    }
+
+    pub fn get_i64(&self, key: &str) -> Result<i64, GetError> {
+        self.get_with(key, "integer", parse_integer)
+    }
+
+    // 負の数は Invalid ではなく OutOfRange になる
+    pub fn get_u64(&self, key: &str) -> Result<u64, GetError> {
+        self.get_with(key, "u64", parse_integer)
+    }
+
+    // FromStr の書き方に従う。範囲外かどうかは区別できないのですべて Invalid になる
+    pub fn get_as<T: FromStr>(&self, key: &str) -> Result<T, GetError> {
+        self.get_with(key, std::any::type_name::<T>(), |value| {
+            value.parse().map_err(|_| ValueError::Invalid)
+        })
+    }
+
+    // スキーマの型で取り出す。SchemaEntry::value_type と組み合わせて使う
+    pub fn get_parsed(&self, key: &str, value_type: &ValueType) -> Result<Value, GetError> {
+        self.get_with(key, &value_type.to_string(), |value| {
+            value_type.parse(value)
+        })
+    }
+
+    fn get_with<T>(
+        &self,
+        key: &str,
+        expected: &str,
+        parse: impl FnOnce(&str) -> Result<T, ValueError>,
+    ) -> Result<T, GetError> {
+        let entry = self.entry(key).ok_or_else(|| GetError::Missing {
+            key: key.to_string(),
+        })?;
+        parse(entry.value()).map_err(|error| {
+            conversion_error(key, expected, entry.value(), entry.value_span(), error)
+        })
+    }
+}
+
+pub(crate) fn conversion_error(
+    key: &str,
+    expected: &str,
+    value: &str,
+    span: Span,
+    error: ValueError,
+) -> GetError {
+    let key = key.to_string();
+    let expected = expected.to_string();
+    let value = value.to_string();
+    match error {
+        ValueError::Invalid => GetError::Invalid {
+            key,
+            expected,
+            value,
+            span,
+        },
+        ValueError::OutOfRange => GetError::OutOfRange {
+            key,
+            expected,
+            value,
+            span,
+        },
+    }
+}
+
+// === 検証済みの Config ===
+//
+// validate_typed がスキーマの型に変換した値。TypedConfig を持っていれば、
+// スキーマの各キーについて値・既定値・省略のどれかが決まっており、型も合っている。
+// 設定のグロブのキー（net.ipv4.conf.*.rp_filter = 1 など）はスキーマのキーに展開した分だけを含む。
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypedEntry {
+    // 設定に書かれた値（グロブで与えられた値を含む）。span は設定ファイルの値を指す
+    Set { value: Value, span: Span },
+    // 設定に無いのでスキーマの既定値を使った。span はスキーマファイルのキーを指す
+    Default { value: Value, span: Span },
+    // 省略可能なキーが設定に無い
+    Absent,
+}
+
+impl TypedEntry {
+    pub fn value(&self) -> Option<&Value> {
+        match self {
+            TypedEntry::Set { value, .. } | TypedEntry::Default { value, .. } => Some(value),
+            TypedEntry::Absent => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TypedConfig {
+    entries: HashMap<String, TypedEntry>,
+}
+
+impl TypedConfig {
+    // 既定値も返す。Absent とスキーマに無いキーは None
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.entry(key).and_then(TypedEntry::value)
+    }
+
+    pub fn entry(&self, key: &str) -> Option<&TypedEntry> {
+        self.entries.get(normalize_key(key).as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &TypedEntry)> {
+        self.entries
+            .iter()
+            .map(|(key, entry)| (key.as_str(), entry))
+    }
+}
+
+// validate と同じ検査をして、通れば値を変換した TypedConfig を返す。
+// ignore_error 付きで型が合わない値は書かれていないものとして扱い、
+// 既定値も無い必須のキーならその TypeMismatch をエラーにする
+pub fn validate_typed(
+    config: &Config,
+    schema: &Schema,
+) -> Result<TypedConfig, Vec<ValidationError>> {
+    validate(config, schema)?;
+    let mut typed = TypedConfig::default();
+    let mut errors = Vec::new();
+    // エラーが毎回同じ順になるよう、キーの順に見る
+    let mut keys: Vec<_> = schema.entries.iter().collect();
+    keys.sort_by_key(|(key, _)| *key);
+    for (key, schema_entry) in keys {
+        let vt = &schema_entry.value_type;
+        let entry = config.entry(key).or_else(|| config.glob_for(key));
+        let parsed = entry.map(|e| (e, vt.parse(e.value())));
+        let typed_entry = match (parsed, &schema_entry.default) {
+            (Some((entry, Ok(value))), _) => TypedEntry::Set {
+                value,
+                span: entry.value_span(),
+            },
+            (_, Some(default)) => TypedEntry::Default {
+                value: default.clone(),
+                span: schema_entry.key_span,
+            },
+            _ if schema_entry.optional => TypedEntry::Absent,
+            // 必須のキーが無ければ validate が MissingKey を返している
+            (None, None) => continue,
+            (Some((entry, Err(_))), None) => {
+                errors.push(ValidationError::TypeMismatch {
+                    key: key.clone(),
+                    expected: vt.to_string(),
+                    got: entry.value().to_string(),
+                    span: entry.value_span(),
+                });
+                continue;
+            }
+        };
+        typed.entries.insert(key.clone(), typed_entry);
+    }
+    // スキーマのパターンにマッチするキー。型が合わないものは書かれていないものとして扱う
+    for (key, entry) in config.iter() {
+        if schema.entries.contains_key(key) {
+            continue;
+        }
+        if let Some(schema_entry) = schema.lookup(key)
+            && let Ok(value) = schema_entry.value_type.parse(entry.value())
+        {
+            let span = entry.value_span();
+            typed
+                .entries
+                .insert(key.clone(), TypedEntry::Set { value, span });
+        }
+    }
+    if errors.is_empty() {
+        Ok(typed)
+    } else {
+        Err(errors)
+    }
+}
+
+// === テスト ===
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn config(value: &str) -> Config {
+        Config::parse(&format!("key = {}", value)).unwrap()
+    }
+
+    proptest! {
+        // 取得できることと検証を通ることが一致する
+        #[test]
+        fn getters_agree_with_is_valid(value in "[-+]?[0-9a-z]{0,22}") {
+            let config = config(&value);
+            prop_assert_eq!(config.get_bool("key").is_ok(), ValueType::Bool.is_valid(&value));
+            prop_assert_eq!(config.get_i64("key").is_ok(), ValueType::Integer.is_valid(&value));
+            for value_type in [ValueType::Str, ValueType::Bool, ValueType::Integer] {
+                prop_assert_eq!(
+                    config.get_parsed("key", &value_type).is_ok(),
+                    value_type.is_valid(&value)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn errors_distinguish_missing_invalid_and_out_of_range() {
+        let content = "flag = yes\nbig = 99999999999999999999\nneg = -1\nport = 8080\n";
+        let config = Config::parse(content).unwrap();
+        assert_eq!(
+            config.get_bool("nope"),
+            Err(GetError::Missing {
+                key: "nope".to_string()
+            })
+        );
+        let Err(GetError::Invalid { expected, span, .. }) = config.get_bool("flag") else {
+            panic!("expected Invalid");
+        };
+        assert_eq!(expected, "bool");
+        assert_eq!(&content[span.range()], "yes");
+
+        assert!(matches!(
+            config.get_i64("big"),
+            Err(GetError::OutOfRange { span, .. }) if span.line == 2
+        ));
+        assert!(matches!(
+            config.get_u64("big"),
+            Err(GetError::OutOfRange { expected, .. }) if expected == "u64"
+        ));
+        assert_eq!(config.get_i64("neg"), Ok(-1));
+        assert!(matches!(
+            config.get_u64("neg"),
+            Err(GetError::OutOfRange { .. })
+        ));
+        assert_eq!(config.get_as::<u16>("port"), Ok(8080));
+        assert!(matches!(
+            config.get_as::<u8>("port"),
+            Err(GetError::Invalid { .. })
+        ));
+        assert_eq!(
+            config.get_parsed("port", &ValueType::Integer),
+            Ok(Value::Integer(8080))
+        );
+    }
+
+    #[test]
+    fn typed_config_records_set_default_and_absent_keys() {
+        let schema = Schema::parse(
+            "debug = bool?\nretry = integer = 3\nendpoint = string\nnet.ipv4.conf.*.rp_filter = integer\n",
+        )
+        .unwrap();
+        let config =
+            Config::parse("endpoint = localhost\nnet.ipv4.conf.eth0.rp_filter = 2\n").unwrap();
+        let typed = validate_typed(&config, &schema).unwrap();
+        assert!(matches!(
+            typed.entry("endpoint"),
+            Some(TypedEntry::Set { value: Value::Str(s), span }) if s == "localhost" && span.line == 1
+        ));
+        assert!(matches!(
+            typed.entry("retry"),
+            Some(TypedEntry::Default { value: Value::Integer(3), span }) if span.line == 2
+        ));
+        assert_eq!(typed.entry("debug"), Some(&TypedEntry::Absent));
+        assert_eq!(typed.get("debug"), None);
+        assert_eq!(
+            typed.get("net/ipv4/conf/eth0/rp_filter"),
+            Some(&Value::Integer(2))
+        );
+        assert_eq!(typed.iter().count(), 4);
+
+        // 検証エラーがあれば TypedConfig は作られない
+        let config = Config::parse("retry = 3").unwrap();
+        assert!(matches!(
+            validate_typed(&config, &schema).unwrap_err()[..],
+            [ValidationError::MissingKey { .. }]
+        ));
+    }
+
+    #[test]
+    fn ignored_type_errors_fall_back_to_defaults() {
+        let schema =
+            Schema::parse("retry = integer = 3\nendpoint = string\nport = integer\n").unwrap();
+        let config = Config::parse("-retry = many\nendpoint = x\n-port = http\n").unwrap();
+        // validate は警告にするが、必須の port には使える値が無い
+        assert!(validate(&config, &schema).is_ok());
+        let errors = validate_typed(&config, &schema).unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [ValidationError::TypeMismatch { key, .. }] if key == "port"
+        ));
+
+        let config = Config::parse("-retry = many\nendpoint = x\nport = 80\n").unwrap();
+        let typed = validate_typed(&config, &schema).unwrap();
+        assert_eq!(typed.get("retry"), Some(&Value::Integer(3)));
+    }
+}
@@ -0,0 +1,247 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    Attribute, Data, DeriveInput, Expr, ExprLit, Fields, GenericArgument, Lit, LitStr, Meta,
+    PathArguments, Type, parse_macro_input,
+};
+
+// === #[derive(SysctlSchema)] ===
+//
+// toy_sysctl_conf::SysctlSchema を実装する。名前付きフィールドの構造体だけに使える。
+//
+//   #[sysctl(rename = "name")]  キーのセグメントをフィールド名の代わりに name にする
+//   #[sysctl(optional)]         省略可能にする（入れ子の構造体ならその下のすべて）
+//
+// フィールドの doc コメントは、スキーマのエントリの直前のコメント（説明）になる。
+
+#[proc_macro_derive(SysctlSchema, attributes(sysctl))]
+pub fn derive_sysctl_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "SysctlSchema can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            &data.fields,
+            "SysctlSchema needs named fields",
+        ));
+    };
+    let mut writes = Vec::new();
+    for field in &fields.named {
+        let options = FieldOptions::parse(&field.attrs)?;
+        let name = match options.rename {
+            Some(rename) => rename.value(),
+            None => field.ident.as_ref().unwrap().to_string(),
+        };
+        let name = name.strip_prefix("r#").unwrap_or(&name).to_string();
+        writes.push(write_field(
+            &name,
+            &field.ty,
+            options.optional,
+            &doc_lines(&field.attrs),
+        ));
+    }
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::toy_sysctl_conf::SysctlSchema for #ident #ty_generics #where_clause {
+            fn write_schema(prefix: &str, optional: bool, out: &mut ::std::string::String) {
+                #(#writes)*
+            }
+        }
+    })
+}
+
+// === フィールドの属性 ===
+
+#[derive(Default)]
+struct FieldOptions {
+    rename: Option<LitStr>,
+    optional: bool,
+}
+
+impl FieldOptions {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut options = FieldOptions::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("sysctl")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    options.rename = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("optional") {
+                    options.optional = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `rename = \"...\"` or `optional`"))
+                }
+            })?;
+        }
+        Ok(options)
+    }
+}
+
+// /// の行。先頭の空白1つを除く
+fn doc_lines(attrs: &[Attribute]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter(|a| a.path().is_ident("doc"))
+        .filter_map(|a| match &a.meta {
+            Meta::NameValue(nv) => match &nv.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(s), ..
+                }) => Some(s.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .map(|line| line.strip_prefix(' ').unwrap_or(&line).to_string())
+        .collect()
+}
+
+// === 型の分類 ===
+
+enum Kind<'a> {
+    // スキーマの型名（"bool"、"integer"、"string"）
+    Value(&'static str),
+    Nested(&'a Type),
+    // HashMap / BTreeMap の値の型。キーはパターンの * になる
+    Map(&'a Type),
+}
+
+// Option<T> なら T と true
+fn unwrap_option(ty: &Type) -> (&Type, bool) {
+    match generic_argument(ty, "Option", 0) {
+        Some(inner) => (unwrap_option(inner).0, true),
+        None => (ty, false),
+    }
+}
+
+// 最後のセグメントが name の型の index 番目の型引数
+fn generic_argument<'a>(ty: &'a Type, name: &str, index: usize) -> Option<&'a Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != name {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    args.args
+        .iter()
+        .filter_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        })
+        .nth(index)
+}
+
+fn classify(ty: &Type) -> Kind<'_> {
+    let path = match ty {
+        Type::Reference(reference) => return classify(&reference.elem),
+        Type::Tuple(_) | Type::Array(_) | Type::Slice(_) => return Kind::Value("string"),
+        Type::Path(path) => path,
+        _ => return Kind::Nested(ty),
+    };
+    for map in ["HashMap", "BTreeMap"] {
+        if let Some(value) = generic_argument(ty, map, 1) {
+            return Kind::Map(value);
+        }
+    }
+    let Some(segment) = path.path.segments.last() else {
+        return Kind::Nested(ty);
+    };
+    match segment.ident.to_string().as_str() {
+        "bool" => Kind::Value("bool"),
+        "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64" | "u128"
+        | "usize" => Kind::Value("integer"),
+        "String" | "str" | "PathBuf" | "Path" | "char" | "f32" | "f64" | "Vec" => {
+            Kind::Value("string")
+        }
+        _ => Kind::Nested(ty),
+    }
+}
+
+// === 生成するコード ===
+
+fn write_field(name: &str, ty: &Type, optional: bool, doc: &[String]) -> TokenStream2 {
+    let (ty, is_option) = unwrap_option(ty);
+    let optional = optional || is_option;
+    let comment: String = doc.iter().map(|line| comment_line(line)).collect();
+    match classify(ty) {
+        Kind::Value(type_name) => quote! {
+            out.push_str(#comment);
+            out.push_str(&::std::format!(
+                "{}{} = {}{}\n",
+                prefix,
+                #name,
+                #type_name,
+                if optional || #optional { "?" } else { "" },
+            ));
+        },
+        Kind::Nested(nested) => {
+            let write = nested_write(nested, &format!("{}.", name), optional);
+            section(&comment, write)
+        }
+        Kind::Map(value) => {
+            let (value, is_option) = unwrap_option(value);
+            let optional = optional || is_option;
+            let write = match classify(value) {
+                Kind::Value(type_name) => quote! {
+                    out.push_str(&::std::format!(
+                        "{}{}.* = {}{}\n",
+                        prefix,
+                        #name,
+                        #type_name,
+                        if optional || #optional { "?" } else { "" },
+                    ));
+                },
+                Kind::Nested(nested) => nested_write(nested, &format!("{}.*.", name), optional),
+                // マップのマップはパターンのセグメントを重ねる
+                Kind::Map(_) => write_field(&format!("{}.*", name), value, optional, &[]),
+            };
+            section(&comment, write)
+        }
+    }
+}
+
+fn nested_write(ty: &Type, segment: &str, optional: bool) -> TokenStream2 {
+    quote! {
+        <#ty as ::toy_sysctl_conf::SysctlSchema>::write_schema(
+            &::std::format!("{}{}", prefix, #segment),
+            optional || #optional,
+            out,
+        );
+    }
+}
+
+// 入れ子の doc コメントは最初のエントリの説明にならないよう、空行で区切る
+fn section(comment: &str, write: TokenStream2) -> TokenStream2 {
+    if comment.is_empty() {
+        return write;
+    }
+    let comment = format!("{}\n", comment);
+    quote! {
+        out.push_str(#comment);
+        #write
+    }
+}
+
+fn comment_line(line: &str) -> String {
+    if line.is_empty() {
+        "#\n".to_string()
+    } else {
+        format!("# {}\n", line)
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use toy_sysctl_conf::{Config, SysctlSchema, ValueType, validate};
+
+// === #[derive(SysctlSchema)] ===
+
+#[derive(SysctlSchema)]
+pub struct Settings {
+    /// 接続先
+    ///
+    /// host:port の形式
+    pub endpoint: String,
+    pub retry: Option<u8>,
+    #[sysctl(rename = "debug_mode", optional)]
+    pub debug: bool,
+    /// ログの設定
+    pub log: Log,
+    pub net: Net,
+}
+
+#[derive(SysctlSchema)]
+pub struct Log {
+    pub file: PathBuf,
+    pub level: String,
+}
+
+#[derive(SysctlSchema)]
+pub struct Net {
+    pub ipv4: Ipv4,
+}
+
+#[derive(SysctlSchema)]
+pub struct Ipv4 {
+    pub conf: HashMap<String, Iface>,
+    pub ip_local_port_range: (u16, u16),
+}
+
+#[derive(SysctlSchema)]
+pub struct Iface {
+    pub rp_filter: i64,
+}
+
+#[test]
+fn struct_definition_becomes_schema_text() {
+    assert_eq!(
+        Settings::schema_text(),
+        "\
+# 接続先
+#
+# host:port の形式
+endpoint = string
+retry = integer?
+debug_mode = bool?
+# ログの設定
+
+log.file = string
+log.level = string
+net.ipv4.conf.*.rp_filter = integer
+net.ipv4.ip_local_port_range = string
+"
+    );
+}
+
+#[test]
+fn derived_schema_validates_configs() {
+    let schema = Settings::schema().unwrap();
+    let endpoint = schema.entry("endpoint").unwrap();
+    assert_eq!(endpoint.description(), Some("接続先\n\nhost:port の形式"));
+    assert!(!endpoint.is_optional());
+    assert!(schema.entry("debug_mode").unwrap().is_optional());
+    assert_eq!(schema.entry("log.file").unwrap().description(), None);
+    assert_eq!(
+        schema
+            .lookup("net.ipv4.conf.eth0.rp_filter")
+            .unwrap()
+            .value_type(),
+        &ValueType::Integer
+    );
+
+    let config = Config::parse(
+        "endpoint = localhost:3000\nlog.file = /var/log/console.log\nlog.level = info\n\
+         net.ipv4.conf.eth0.rp_filter = 1\nnet.ipv4.ip_local_port_range = 32768 60999\n",
+    )
+    .unwrap();
+    assert!(validate(&config, &schema).is_ok());
+    let config = Config::parse("endpoint = x\nretry = many\n").unwrap();
+    assert!(validate(&config, &schema).is_err());
+}